@@ -0,0 +1,19 @@
+#![no_main]
+
+use cpm::frequency::{Frequency, FrequencyRange};
+use libfuzzer_sys::fuzz_target;
+
+// Run with: cargo +nightly fuzz run parse_frequency
+fuzz_target!(|input: &str| {
+    let length = input.chars().count();
+
+    match input.parse::<Frequency>() {
+        Ok(frequency) => assert_eq!(frequency.to_string().parse::<Frequency>(), Ok(frequency)),
+        Err(error) => assert!(error.position <= length),
+    }
+
+    match input.parse::<FrequencyRange>() {
+        Ok(range) => assert_eq!(range.to_string().parse::<FrequencyRange>(), Ok(range)),
+        Err(error) => assert!(error.position <= length),
+    }
+});
@@ -1,343 +1,1401 @@
 use anyhow as ah;
 use std::env::{self};
+use std::time::Duration;
+
+use crate::{
+    attributes::{self, Attribute, Value, ATTRIBUTES},
+    backend::{CpufreqBackend, Sysfs},
+    cpuidle, cpulist, epb,
+    error::CpmError,
+    explain::{self, Explanation},
+    frequency::{parse_freq, Frequency, FrequencyRange, Snap},
+    help, hotplug, lock, platform,
+    policies::PolicyDir,
+    profiles, smt, suggest,
+    uncore::UncoreDir,
+    CPU_FREQ_PATH,
+};
+
+/// Fails on the first argument left over once a command has taken the ones
+/// it understands, so that a mistyped or misplaced argument isn't ignored.
+fn expect_end(args: &mut std::vec::IntoIter<String>) -> ah::Result<()> {
+    match args.next() {
+        Some(arg) => ah::bail!("Unexpected argument '{}'.", arg),
+        None => Ok(()),
+    }
+}
+
+/// Returns an error for a value that isn't one of the choices, suggesting the
+/// closest choice if one is close enough to be a typo.
+fn unrecognized(what: &str, value: &str, choices: &[&str]) -> ah::Error {
+    ah::anyhow!(
+        "Unrecognized {} '{}'.{}\nValid values: {}",
+        what,
+        value,
+        suggest::did_you_mean(value, choices.iter().copied()),
+        choices.join(", ")
+    )
+}
+
+/// Returns an error for an attribute name that isn't in the registry,
+/// suggesting the closest name, written the way it's typed, with words
+/// separated by spaces. Besides aliases, the file names, the spelled out
+/// current and available forms, and gov current and the like are candidates,
+/// since find accepts them too.
+fn unrecognized_attribute(name: &str, special: &[&str], writable_only: bool) -> ah::Error {
+    let mut names: Vec<String> = special.iter().map(|name| name.to_string()).collect();
+
+    for attribute in ATTRIBUTES.iter().filter(|a| a.writable || !writable_only) {
+        names.push(attribute.file_name.to_string());
+        names.push(attribute.alias.to_string());
+        names.push(
+            attribute
+                .alias
+                .replace("curr", "current")
+                .replace("avail", "available"),
+        );
+
+        if attribute.writable && !attribute.alias.contains('.') {
+            names.push(format!("{}.current", attribute.alias));
+        }
+    }
+
+    let typed = |name: &str| name.replace('.', " ");
+
+    ah::anyhow!(
+        "Unrecognized attribute '{}'.{}",
+        typed(name),
+        suggest::closest(name, names.iter().map(String::as_str))
+            .map(|closest| format!(" Did you mean '{}'?", typed(closest)))
+            .unwrap_or_default()
+    )
+}
+
+/// Filters the policy directories down to the ones matched by a policy
+/// identifier, which is either a single number, a range (0:4), a list of
+/// numbers (0,2,5), or all / * for every policy. Policies that are inactive
+/// because their CPUs are offline are left out of all / *.
+pub fn select_policies<B: CpufreqBackend>(
+    policy_dirs: Vec<PolicyDir<B>>,
+    target_policy: &str,
+) -> ah::Result<Vec<PolicyDir<B>>> {
+    let policy_dir_numbers: Vec<u32> = match target_policy {
+        "*" | "all" => policy_dirs
+            .iter()
+            .filter(|pd| pd.is_active())
+            .map(|pd| pd.policy_number)
+            .collect(),
+        num_str => cpulist::parse(num_str)
+            .map_err(|e| ah::anyhow!("Invalid policy identifier provided. {}", e))?,
+    };
+
+    Ok(policy_dirs
+        .into_iter()
+        .filter(|pd| policy_dir_numbers.contains(&pd.policy_number))
+        .collect())
+}
 
-use crate::{frequency::Frequency, policies::PolicyDir, CPU_FREQ_PATH};
+/// Resolves a CPU selector to CPU numbers. Selectors prefixed with "cpu" name
+/// CPUs directly, e.g. cpu3 or cpu0-3, and anything else is a policy identifier
+/// resolving to the affected_cpus of every matching policy.
+pub fn select_cpus<B: CpufreqBackend>(
+    policy_dirs: Vec<PolicyDir<B>>,
+    target: &str,
+) -> ah::Result<Vec<u32>> {
+    if let Some(cpu_list) = target.strip_prefix("cpu") {
+        return Ok(cpulist::parse(cpu_list)?);
+    }
 
-pub fn parse_freq_value(freq_str: &str) -> ah::Result<(Option<Frequency>, Option<Frequency>)> {
-    let freq_str = freq_str.replace(',', "");
-    let parts: Vec<&str> = freq_str.split(':').collect();
+    let mut cpus: Vec<u32> = Vec::new();
 
-    if parts.len() < 2 {
-        ah::bail!("Invalid frequency value provided. Must be in the format of <min>:<max>, :<max>, or <min>:");
+    for policy_dir in select_policies(policy_dirs, target)? {
+        cpus.extend(policy_dir.read_affected_cpus()?);
     }
 
-    let min_freq = parts[0].to_string();
-    let mut min_freq_val: Option<Frequency> = None;
+    cpus.sort_unstable();
+    cpus.dedup();
 
-    if !min_freq.is_empty() {
-        let mut suffix: Option<char> = min_freq.chars().last();
+    Ok(cpus)
+}
 
-        if let Some(s) = suffix {
-            if !s.is_alphabetic() {
-                suffix = None;
-            }
+/// Resolves a frequency to one of the policy's discrete steps, noting the
+/// step when it differs. With a snap mode the step is returned to be
+/// written, otherwise the frequency is written as given, and the noted step
+/// is the one the kernel rounds it to in the default direction.
+fn resolve_step<B: CpufreqBackend>(
+    policy_dir: &PolicyDir<B>,
+    description: &str,
+    frequency: &Frequency,
+    snap: Option<Snap>,
+    default: Snap,
+    notes: &mut Vec<String>,
+) -> ah::Result<Frequency> {
+    let step = policy_dir.resolve_frequency(frequency, snap.unwrap_or(default))?;
+
+    if step != *frequency {
+        notes.push(format!(
+            "Policy {} {} {} {} step {}",
+            policy_dir.policy_number,
+            description,
+            frequency,
+            if snap.is_some() {
+                "snapped to"
+            } else {
+                "resolves to"
+            },
+            step
+        ));
+    }
+
+    Ok(if snap.is_some() { step } else { *frequency })
+}
+
+/// Sets a single attribute of a policy, e.g. freq to 2.5:3.5 or gov to
+/// powersave, validating the value against what the policy supports. Besides
+/// freq and epb, any writable attribute in the registry can be named. Snap
+/// rounds frequencies to the policy's discrete steps, if it has any. Returns
+/// notes on the steps frequencies resolved to, for the caller to print once
+/// the value has been accepted.
+pub fn apply_attribute<B: CpufreqBackend>(
+    policy_dir: &PolicyDir<B>,
+    attrib: &str,
+    value: &str,
+    snap: Option<Snap>,
+) -> ah::Result<Vec<String>> {
+    let mut notes = Vec::new();
+
+    match attrib.to_lowercase().as_str() {
+        "freq" => {
+            let FrequencyRange { min, max } =
+                value.parse::<FrequencyRange>().map_err(CpmError::from)?;
+
+            // Without --snap the driver may adjust the value to a supported step.
+            let min = min
+                .map(|f| {
+                    resolve_step(
+                        policy_dir,
+                        "scaling min frequency",
+                        &f,
+                        snap,
+                        Snap::Up,
+                        &mut notes,
+                    )
+                })
+                .transpose()?;
+
+            let max = max
+                .map(|f| {
+                    resolve_step(
+                        policy_dir,
+                        "scaling max frequency",
+                        &f,
+                        snap,
+                        Snap::Down,
+                        &mut notes,
+                    )
+                })
+                .transpose()?;
+
+            policy_dir.set_scaling_range(min.as_ref(), max.as_ref())?;
+        }
+        "epb" => {
+            let bias = epb::parse_epb(value)?;
+            policy_dir.set_energy_perf_bias(bias)?;
         }
+        name => {
+            let attribute = attributes::find(name)
+                .ok_or_else(|| unrecognized_attribute(name, &["freq", "epb", "raw"], true))?;
 
-        match suffix {
-            Some('g') => min_freq_val = Some(Frequency::GHz(min_freq.parse::<f64>()?)),
-            Some('m') => min_freq_val = Some(Frequency::MHz(min_freq.parse::<u64>()?)),
-            Some('k') => min_freq_val = Some(Frequency::KHz(min_freq.parse::<u64>()?)),
-            Some('h') => min_freq_val = Some(Frequency::Hz(min_freq.parse::<u64>()?)),
-            Some(_) => ah::bail!(
-                "Invalid suffix provided for frequency value.\nValid suffixes are: g, m, k, h"
-            ),
-            None if min_freq.contains('.') => {
-                min_freq_val = Some(Frequency::GHz(min_freq.parse::<f64>()?))
-            }
-            None => min_freq_val = Some(Frequency::KHz(min_freq.parse::<u64>()?)),
+            policy_dir.set_value(attribute, value)?;
         }
     }
 
-    let max_freq = parts[1].to_string();
-    let mut max_freq_val: Option<Frequency> = None;
+    Ok(notes)
+}
 
-    if !max_freq.is_empty() {
-        let mut suffix: Option<char> = max_freq.chars().last();
+/// The attributes cpm get prints for a policy when no attribute, or all, is
+/// given, in the order they're printed.
+static GET_ALL_ATTRIBUTES: [&Attribute; 9] = [
+    &attributes::SCALING_MIN_FREQ,
+    &attributes::SCALING_MAX_FREQ,
+    &attributes::SCALING_CUR_FREQ,
+    &attributes::CPUINFO_MIN_FREQ,
+    &attributes::CPUINFO_MAX_FREQ,
+    &attributes::SCALING_GOVERNOR,
+    &attributes::SCALING_AVAILABLE_GOVERNORS,
+    &attributes::ENERGY_PERFORMANCE_PREFERENCE,
+    &attributes::SCALING_DRIVER,
+];
+
+/// Formats a value of an attribute for display, with the performance profile
+/// along with its raw EPP value.
+fn describe_value<B: CpufreqBackend>(
+    policy_dir: &PolicyDir<B>,
+    attribute: &Attribute,
+    value: &Value,
+) -> String {
+    let described = attribute.format(value);
+
+    if attribute.file_name != attributes::ENERGY_PERFORMANCE_PREFERENCE.file_name {
+        return described;
+    }
 
-        if let Some(s) = suffix {
-            if !s.is_alphabetic() {
-                suffix = None;
-            }
+    match policy_dir.read_current_epp_value() {
+        Ok((epp, name)) if name == described => format!("{} ({})", name, epp),
+        Ok((epp, name)) => format!("{} (nearest: {})", epp, name),
+        Err(_) => described,
+    }
+}
+
+/// Formats every attribute in GET_ALL_ATTRIBUTES of a policy, one per line.
+/// Attributes the policy doesn't have, e.g. EPP with acpi-cpufreq, are n/a.
+pub fn format_all_attributes<B: CpufreqBackend>(policy_dir: &PolicyDir<B>) -> String {
+    let name_width = GET_ALL_ATTRIBUTES
+        .iter()
+        .map(|attribute| attribute.description.len())
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = vec![format!("Policy {}\n", policy_dir.policy_number)];
+
+    for attribute in GET_ALL_ATTRIBUTES {
+        let value = match policy_dir.read_value(attribute) {
+            Ok(value) => describe_value(policy_dir, attribute, &value),
+            Err(_) => "n/a".to_string(),
+        };
+
+        lines.push(format!(
+            "    {:<width$}  {}",
+            attribute.description,
+            value,
+            width = name_width
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Formats an attribute of a policy for display. Lists are printed one entry
+/// per line, and the performance profile along with its raw EPP value.
+pub fn format_attribute<B: CpufreqBackend>(
+    policy_dir: &PolicyDir<B>,
+    attribute: &Attribute,
+) -> ah::Result<String> {
+    if attribute.file_name == attributes::SCALING_AVAILABLE_FREQUENCIES.file_name {
+        let steps = policy_dir.read_available_frequencies()?;
+
+        if steps.is_empty() {
+            return Ok(format!(
+                "Policy {} has no discrete frequency steps.",
+                policy_dir.policy_number
+            ));
         }
 
-        match suffix {
-            Some('g') => max_freq_val = Some(Frequency::GHz(max_freq.parse::<f64>()?)),
-            Some('m') => max_freq_val = Some(Frequency::MHz(max_freq.parse::<u64>()?)),
-            Some('k') => max_freq_val = Some(Frequency::KHz(max_freq.parse::<u64>()?)),
-            Some('h') => max_freq_val = Some(Frequency::Hz(max_freq.parse::<u64>()?)),
-            Some(_) => ah::bail!(
-                "Invalid suffix provided for frequency value.\nValid suffixes are: g, m, k, h"
-            ),
-            None if max_freq.contains('.') => {
-                max_freq_val = Some(Frequency::GHz(max_freq.parse::<f64>()?))
-            }
-            None => max_freq_val = Some(Frequency::KHz(max_freq.parse::<u64>()?)),
+        let mut list = format!(
+            "Policy {} {}...\n\n",
+            policy_dir.policy_number, attribute.description
+        );
+
+        for (i, step) in steps.iter().enumerate() {
+            list += &format!("{}.) - {}\n", i, step.to_ghz());
         }
+
+        return Ok(list);
     }
 
-    Ok((min_freq_val, max_freq_val))
+    let value = policy_dir.read_value(attribute)?;
+
+    if let Value::List(entries) = &value {
+        let mut list = format!(
+            "Policy {} {}...\n\n",
+            policy_dir.policy_number, attribute.description
+        );
+
+        for (i, entry) in entries.iter().enumerate() {
+            list += &format!("{}.) - {}\n", i, entry);
+        }
+
+        return Ok(list);
+    }
+
+    Ok(format!(
+        "Policy {} {}: {}",
+        policy_dir.policy_number,
+        attribute.description,
+        describe_value(policy_dir, attribute, &value)
+    ))
 }
 
-pub fn op_set(
-    policy_dirs: Vec<PolicyDir>,
+/// An attribute and the value to set it to, or for raw, a file name and the
+/// contents to write to it.
+struct Setting {
+    attrib: String,
+    value: String,
+    raw_contents: Option<String>,
+}
+
+impl Setting {
+    /// Raw writes are labelled with the file they write to.
+    fn label(&self) -> String {
+        match self.raw_contents {
+            Some(_) => self.value.clone(),
+            None => self.attrib.to_lowercase(),
+        }
+    }
+
+    fn apply<B: CpufreqBackend>(
+        &self,
+        policy_dir: &PolicyDir<B>,
+        snap: Option<Snap>,
+    ) -> ah::Result<Vec<String>> {
+        match &self.raw_contents {
+            Some(contents) => {
+                policy_dir.write_raw(&self.value, contents)?;
+                Ok(Vec::new())
+            }
+            None => apply_attribute(policy_dir, &self.attrib, &self.value, snap),
+        }
+    }
+}
+
+pub fn op_set<B: CpufreqBackend>(
+    backend: &B,
+    policy_dirs: Vec<PolicyDir<B>>,
     args: &mut std::vec::IntoIter<String>,
 ) -> ah::Result<()> {
     let target_policy = args
         .next()
         .ok_or(ah::anyhow!("No policy specified to set."))?;
 
-    let target_attrib = args
-        .next()
-        .ok_or(ah::anyhow!("No target specified to set."))?;
+    if target_policy == "platform" {
+        let target_profile = args
+            .next()
+            .ok_or(ah::anyhow!("No platform profile specified to set."))?;
 
-    let target_value = args
-        .next()
-        .ok_or(ah::anyhow!("No value specified to set."))?;
+        return Ok(platform::set_platform_profile(
+            backend,
+            &target_profile.to_lowercase(),
+        )?);
+    }
+
+    let mut settings = Vec::new();
+    let mut snap = None;
+    let mut keep_going = false;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--snap" => {
+                let mode = args
+                    .next()
+                    .ok_or(ah::anyhow!("No mode specified for --snap."))?;
+                snap = Some(mode.parse::<Snap>()?);
+            }
+            "--keep-going" => keep_going = true,
+            flag if flag.starts_with("--") => {
+                return Err(unrecognized("option", flag, &["--snap", "--keep-going"]))
+            }
+            attrib => {
+                let value = args
+                    .next()
+                    .ok_or(ah::anyhow!("No value specified to set {}.", attrib))?;
+
+                let raw_contents = match attrib {
+                    "raw" => Some(
+                        args.next()
+                            .ok_or(ah::anyhow!("No value specified to write to '{}'.", value))?,
+                    ),
+                    _ => None,
+                };
+
+                settings.push(Setting {
+                    attrib: attrib.to_string(),
+                    value,
+                    raw_contents,
+                });
+            }
+        }
+    }
 
-    let mut policy_dir_numbers: Vec<u32> = Vec::new();
+    if settings.is_empty() {
+        ah::bail!("No target specified to set.");
+    }
 
-    // Collect the policy directory numbers identifiers that we're targeting.
-    match target_policy.as_str() {
-        "*" | "all" => policy_dir_numbers = policy_dirs.iter().map(|pd| pd.policy_number).collect(),
+    let target_policy_dirs = select_policies(policy_dirs, &target_policy)?;
 
-        num_str if num_str.contains(':') => {
-            let parts: Vec<&str> = num_str.split(':').collect();
+    if target_policy_dirs.is_empty() {
+        ah::bail!("No policies matched '{}'.", target_policy);
+    }
 
-            if parts.len() < 2 {
-                ah::bail!("Invalid policy identifier provided.");
-            }
+    // Every setting is first applied to a dry run of its policy, which checks
+    // it against the policy and records its writes, seeing the writes of the
+    // settings before it. Only once all of them pass are the writes made.
+    let mut results = Vec::new();
+    let mut planned = Vec::new();
 
-            let start = parts[0].parse::<u32>()?;
-            let end = parts[1].parse::<u32>()?;
+    for policy_dir in &target_policy_dirs {
+        let dry_run = policy_dir.dry_run();
 
-            if end > start {
-                ah::bail!("The end policy number must be greater than the start policy number.");
-            }
+        for (index, setting) in settings.iter().enumerate() {
+            let recorded = dry_run.backend.writes().len();
 
-            policy_dir_numbers = (start..=end).collect();
+            match setting.apply(&dry_run, snap) {
+                Ok(notes) => {
+                    let writes = dry_run.backend.writes().split_off(recorded);
+                    planned.push((policy_dir, index, writes, notes));
+                }
+                Err(e) if keep_going => results.push((policy_dir.policy_number, index, Err(e))),
+                Err(e) => return Err(e),
+            }
         }
+    }
 
-        num_str if num_str.contains(',') => {
-            let parts: Vec<&str> = num_str.split(',').collect();
+    for (policy_dir, index, writes, notes) in planned {
+        let result = policy_dir.replay(&writes).map_err(ah::Error::from);
 
-            for part in parts {
-                let num = part.parse::<u32>()?;
-                policy_dir_numbers.push(num);
+        if result.is_ok() {
+            for note in notes {
+                println!("{}", note);
             }
         }
 
-        num_str => policy_dir_numbers.push(
-            num_str
-                .parse::<u32>()
-                .map_err(|_| ah::anyhow!("Invalid policy identifier provided."))?,
-        ),
-    };
+        match keep_going {
+            true => results.push((policy_dir.policy_number, index, result)),
+            false => result?,
+        }
+    }
 
-    let target_policy_dirs: Vec<PolicyDir> = policy_dirs
+    if !keep_going {
+        return Ok(());
+    }
+
+    results.sort_by_key(|(policy_number, index, _)| (*policy_number, *index));
+
+    report_set_results(
+        results
+            .into_iter()
+            .map(|(policy_number, index, result)| (policy_number, settings[index].label(), result))
+            .collect(),
+    )
+}
+
+/// Prints a table of the outcome of every write attempted by set with
+/// --keep-going, then fails with the first error if any write failed, so the
+/// exit code still reflects the kind of failure.
+fn report_set_results(results: Vec<(u32, String, ah::Result<()>)>) -> ah::Result<()> {
+    println!("{}", format_set_results(&results));
+
+    let total = results.len();
+    let failures: Vec<ah::Error> = results
         .into_iter()
-        .filter(|pd| policy_dir_numbers.contains(&pd.policy_number))
+        .filter_map(|(_, _, result)| result.err())
         .collect();
+    let failed = failures.len();
+
+    match failures.into_iter().next() {
+        Some(first) => Err(first.context(format!("{} of {} writes failed", failed, total))),
+        None => Ok(()),
+    }
+}
 
-    for policy_dir in target_policy_dirs {
-        match target_attrib.to_lowercase().as_str() {
-            "freq" => {
-                let frequency = parse_freq_value(&target_value)?;
-                let (min, max) = frequency;
+/// Formats the outcome of every write attempted by set with --keep-going as
+/// a table, one row per policy and setting.
+pub fn format_set_results(results: &[(u32, String, ah::Result<()>)]) -> String {
+    let width = results
+        .iter()
+        .map(|(_, attrib, _)| attrib.len())
+        .chain(Some("Attribute".len()))
+        .max()
+        .unwrap_or(0);
+
+    let mut table = format!(
+        "{:<8} {:<width$} {:<7} {}\n",
+        "Policy",
+        "Attribute",
+        "Result",
+        "Reason",
+        width = width
+    );
+
+    for (policy_number, attrib, result) in results {
+        // Parse errors span several lines to point at the input, the first
+        // line is the reason.
+        let (status, reason) = match result {
+            Ok(()) => ("ok", String::new()),
+            Err(e) => (
+                "error",
+                e.to_string().lines().next().unwrap_or("").to_string(),
+            ),
+        };
+
+        let row = format!(
+            "{:<8} {:<width$} {:<7} {}",
+            policy_number,
+            attrib,
+            status,
+            reason,
+            width = width
+        );
+
+        table += row.trim_end();
+        table.push('\n');
+    }
+
+    table
+}
 
-                if let (None, None) = (min, max) {
-                    ah::bail!("No frequency values provided.");
+pub fn op_get<B: CpufreqBackend>(
+    backend: &B,
+    policy_dirs: Vec<PolicyDir<B>>,
+    args: &mut std::vec::IntoIter<String>,
+) -> ah::Result<()> {
+    let target_policy = args.next().ok_or(ah::anyhow!("No policies specified."))?;
+
+    if target_policy == "platform" {
+        let target_value = args.next().unwrap_or("curr".to_string()).to_lowercase();
+        expect_end(args)?;
+
+        match target_value.as_str() {
+            "curr" | "current" => {
+                println!(
+                    "Current platform profile: {}",
+                    platform::read_platform_profile(backend)?
+                )
+            }
+            "avail" | "available" => {
+                println!("Available platform profiles...\n");
+
+                for (i, choice) in platform::read_platform_profile_choices(backend)?
+                    .iter()
+                    .enumerate()
+                {
+                    println!("{}.) - {}", i, choice);
                 }
+            }
+            value => {
+                return Err(unrecognized(
+                    "platform value",
+                    value,
+                    &["curr", "current", "avail", "available"],
+                ))
+            }
+        }
+
+        return Ok(());
+    }
+
+    // Without an attribute, every attribute worth knowing at a glance is printed.
+    let target_attrib = args.next().unwrap_or("all".to_string()).to_lowercase();
+
+    if target_attrib == "all" {
+        expect_end(args)?;
 
-                if let Some(min) = min {
-                    policy_dir.set_scaling_min_freq(&min)?;
+        let target_policy_dirs = select_policies(policy_dirs, &target_policy)?;
+
+        if target_policy_dirs.is_empty() {
+            ah::bail!("No policies matched '{}'.", target_policy);
+        }
+
+        let output: Vec<String> = target_policy_dirs
+            .iter()
+            .map(format_all_attributes)
+            .collect();
+        println!("{}", output.join("\n--------------------\n"));
+
+        return Ok(());
+    }
+
+    let target_value = args.next();
+    expect_end(args)?;
+
+    // Anything but raw and epb is looked up in the registry, joining the two
+    // words with a period, so freq min resolves to freq.min.
+    let attribute: Option<&Attribute> = match (target_attrib.as_str(), &target_value) {
+        ("raw" | "epb", _) => None,
+        (attrib, value) => {
+            let name = match value {
+                Some(value) => format!("{}.{}", attrib, value.to_lowercase()),
+                None => attrib.to_string(),
+            };
+
+            Some(
+                attributes::find(&name)
+                    .ok_or_else(|| unrecognized_attribute(&name, &["raw", "epb"], false))?,
+            )
+        }
+    };
+
+    if let ("epb", Some(value)) = (target_attrib.as_str(), &target_value) {
+        if !matches!(value.to_lowercase().as_str(), "curr" | "current") {
+            return Err(unrecognized("epb value", value, &["curr", "current"]));
+        }
+    }
+
+    let target_policy_dirs = select_policies(policy_dirs, &target_policy)?;
+
+    if target_policy_dirs.is_empty() {
+        ah::bail!("No policies matched '{}'.", target_policy);
+    }
+
+    let mut output: Vec<String> = Vec::new();
+
+    for (i, policy_dir) in target_policy_dirs.iter().enumerate() {
+        if i > 0 {
+            output.push("--------------------".to_string());
+        }
+
+        if let Some(attribute) = attribute {
+            output.push(format_attribute(policy_dir, attribute)?);
+            continue;
+        }
+
+        match (target_attrib.as_str(), target_value.as_deref()) {
+            ("raw", Some("list")) => {
+                let mut files = format!("Policy {} policy files...\n\n", policy_dir.policy_number);
+
+                for (file_name, writable) in policy_dir.list_raw_files()? {
+                    files += &format!("{} {}\n", if writable { "rw" } else { "r-" }, file_name);
                 }
 
-                if let Some(max) = max {
-                    policy_dir.set_scaling_max_freq(&max)?;
+                output.push(files);
+            }
+
+            ("raw", Some(file_name)) => {
+                // File names are case sensitive, so the value isn't lowercased.
+                let content = policy_dir.read_raw(file_name)?;
+
+                output.push(format!(
+                    "Policy {} {}: {}",
+                    policy_dir.policy_number,
+                    file_name,
+                    content.trim()
+                ));
+            }
+
+            ("raw", None) => ah::bail!("No policy file specified."),
+
+            _ => {
+                for (cpu, bias) in policy_dir.read_energy_perf_bias()? {
+                    output.push(format!(
+                        "Policy {} CPU {} energy performance bias: {}{}",
+                        policy_dir.policy_number,
+                        cpu,
+                        bias,
+                        epb::epb_name(bias)
+                            .map(|n| format!(" ({})", n))
+                            .unwrap_or_default()
+                    ));
                 }
             }
-            "gov" => {
-                let available_govs = policy_dir.read_available_governors()?;
-                let target_gov = target_value.to_lowercase();
-
-                if !available_govs.contains(&target_gov) {
-                    ah::bail!(
-                        "The governor '{}' is not available for policy {}.",
-                        target_gov,
-                        policy_dir.policy_number
+        }
+    }
+
+    for line in output {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+pub fn op_lock<B: CpufreqBackend>(
+    backend: &B,
+    policy_dirs: Vec<PolicyDir<B>>,
+    args: &mut std::vec::IntoIter<String>,
+) -> ah::Result<()> {
+    let target_freq = args
+        .next()
+        .ok_or(ah::anyhow!("No frequency specified to lock to."))?;
+
+    let frequency = parse_freq(&target_freq)?;
+
+    let mut target_policy = String::from("all");
+    let mut disable_idle = false;
+
+    let mut policy_given = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--no-idle" => disable_idle = true,
+            flag if flag.starts_with("--") => {
+                return Err(unrecognized("option", flag, &["--no-idle"]))
+            }
+            _ if policy_given => ah::bail!("Unexpected argument '{}'.", arg),
+            policy => {
+                target_policy = policy.to_string();
+                policy_given = true;
+            }
+        }
+    }
+
+    let target_policy_dirs = select_policies(policy_dirs, &target_policy)?;
+
+    if target_policy_dirs.is_empty() {
+        ah::bail!("No policies matched '{}'.", target_policy);
+    }
+
+    Ok(lock::lock(
+        backend,
+        &target_policy_dirs,
+        &frequency,
+        disable_idle,
+    )?)
+}
+
+pub fn op_unlock<B: CpufreqBackend>(
+    backend: &B,
+    policy_dirs: Vec<PolicyDir<B>>,
+    args: &mut std::vec::IntoIter<String>,
+) -> ah::Result<()> {
+    expect_end(args)?;
+    Ok(lock::unlock(backend, &policy_dirs)?)
+}
+
+pub fn op_idle<B: CpufreqBackend>(
+    backend: &B,
+    policy_dirs: Vec<PolicyDir<B>>,
+    args: &mut std::vec::IntoIter<String>,
+) -> ah::Result<()> {
+    let action = args
+        .next()
+        .ok_or(ah::anyhow!("No idle action specified."))?
+        .to_lowercase();
+
+    if !matches!(action.as_str(), "show" | "disable" | "enable") {
+        return Err(unrecognized(
+            "idle action",
+            &action,
+            &["show", "disable", "enable"],
+        ));
+    }
+
+    let target = args
+        .next()
+        .ok_or(ah::anyhow!("No policies or CPUs specified."))?;
+
+    let selector = match action.as_str() {
+        "show" => None,
+        _ => Some(args.next().ok_or(ah::anyhow!("No idle state specified."))?),
+    };
+
+    expect_end(args)?;
+
+    let cpus = select_cpus(policy_dirs, &target)?;
+
+    if cpus.is_empty() {
+        ah::bail!("No CPUs matched '{}'.", target);
+    }
+
+    match selector {
+        None => {
+            let mut output: Vec<String> = Vec::new();
+
+            for cpu in cpus {
+                let states = cpuidle::read_idle_states(backend, cpu)?;
+
+                if !output.is_empty() {
+                    output.push("--------------------".to_string());
+                }
+
+                if states.is_empty() {
+                    output.push(format!("CPU {} has no idle states.", cpu));
+                    continue;
+                }
+
+                let total_time: u64 = states.iter().map(|s| s.time).sum();
+
+                let mut table = format!(
+                    "CPU {} idle states...\n\n{:<8} {:<10} {:>10} {:>10} {:>12} {:>9}  {}\n",
+                    cpu, "State", "Name", "Latency", "Residency", "Usage", "Time", "Disabled"
+                );
+
+                for state in states {
+                    let time_share = match total_time {
+                        0 => 0.0,
+                        total => state.time as f64 / total as f64 * 100.0,
+                    };
+
+                    table += &format!(
+                        "{:<8} {:<10} {:>7} us {:>7} us {:>12} {:>8.2}%  {}\n",
+                        state.dir_name(),
+                        state.name,
+                        state.latency,
+                        state.residency,
+                        state.usage,
+                        time_share,
+                        if state.disabled { "yes" } else { "no" }
                     );
                 }
 
-                policy_dir.set_governor(&target_gov)?;
+                output.push(table);
             }
-            "perf" => {
-                let available_profiles = policy_dir.read_available_perf_profiles()?;
-                let target_profile = target_value.to_lowercase();
-
-                if !available_profiles.contains(&target_profile) {
-                    ah::bail!(
-                        "The performance profile '{}' is not available for policy {}.",
-                        target_profile,
-                        policy_dir.policy_number
-                    );
+
+            for line in output {
+                println!("{}", line);
+            }
+        }
+
+        Some(selector) => {
+            let disabled = action == "disable";
+
+            for cpu in cpus {
+                let states: Vec<cpuidle::IdleState> = cpuidle::read_idle_states(backend, cpu)?
+                    .into_iter()
+                    .filter(|state| selector == "all" || state.matches(&selector))
+                    .collect();
+
+                if states.is_empty() {
+                    ah::bail!("CPU {} has no idle state matching '{}'.", cpu, selector);
                 }
 
-                policy_dir.set_perf_profile(&target_profile)?;
+                for state in states {
+                    state.set_disabled(backend, disabled)?;
+                }
             }
-            _ => (),
         }
     }
 
     Ok(())
 }
 
-pub fn op_get(
-    policy_dirs: Vec<PolicyDir>,
+pub fn op_cpu<B: CpufreqBackend>(
+    backend: &B,
     args: &mut std::vec::IntoIter<String>,
 ) -> ah::Result<()> {
-    let target_policy = args.next().ok_or(ah::anyhow!("No policies specified."))?;
+    let action = args
+        .next()
+        .ok_or(ah::anyhow!("No CPU action specified."))?
+        .to_lowercase();
 
-    let target_attrib = args.next().ok_or(ah::anyhow!("No attribute specified."))?;
+    if !matches!(action.as_str(), "online" | "offline") {
+        return Err(unrecognized("CPU action", &action, &["online", "offline"]));
+    }
 
-    let target_value = args.next().ok_or(ah::anyhow!("No value specified."))?;
+    let target = args.next().ok_or(ah::anyhow!("No CPUs specified."))?;
 
-    let mut policy_dir_numbers: Vec<u32> = Vec::new();
+    let mut force = false;
 
-    // Collect the policy directory numbers identifiers that we're targeting.
-    match target_policy.as_str() {
-        "*" | "all" => policy_dir_numbers = policy_dirs.iter().map(|pd| pd.policy_number).collect(),
+    for arg in args {
+        match arg.as_str() {
+            "--force" => force = true,
+            flag if flag.starts_with("--") => {
+                return Err(unrecognized("option", flag, &["--force"]))
+            }
+            _ => ah::bail!("Unexpected argument '{}'.", arg),
+        }
+    }
 
-        num_str if num_str.contains(':') => {
-            let parts: Vec<&str> = num_str.split(':').collect();
+    let cpus = match target.as_str() {
+        "*" | "all" => hotplug::read_present_cpus(backend)?,
+        cpu_list => cpulist::parse(cpu_list)?,
+    };
 
-            if parts.len() < 2 {
-                ah::bail!("Invalid policy identifier provided.");
-            }
+    match action.as_str() {
+        "online" => hotplug::set_online(backend, &cpus)?,
+        _ => hotplug::set_offline(backend, &cpus, force)?,
+    }
 
-            let start = parts[0].parse::<u32>()?;
-            let end = parts[1].parse::<u32>()?;
+    Ok(())
+}
 
-            if end > start {
-                ah::bail!("The end policy number must be greater than the start policy number.");
+pub fn op_smt<B: CpufreqBackend>(
+    backend: &B,
+    policy_dirs: Vec<PolicyDir<B>>,
+    args: &mut std::vec::IntoIter<String>,
+) -> ah::Result<()> {
+    let action = args
+        .next()
+        .ok_or(ah::anyhow!("No SMT action specified."))?
+        .to_lowercase();
+    expect_end(args)?;
+
+    if action != "status" && !smt::SMT_CONTROL_VALUES.contains(&action.as_str()) {
+        return Err(unrecognized(
+            "SMT action",
+            &action,
+            &["on", "off", "forceoff", "status"],
+        ));
+    }
+
+    if action == "status" {
+        println!("SMT control: {}", smt::read_control(backend)?);
+        println!(
+            "SMT active: {}",
+            if smt::read_active(backend)? {
+                "yes"
+            } else {
+                "no"
             }
+        );
+        return Ok(());
+    }
 
-            policy_dir_numbers = (start..=end).collect();
-        }
+    let before = smt::policy_cpus(&policy_dirs)?;
+    smt::set_control(backend, &action)?;
 
-        num_str if num_str.contains(',') => {
-            let parts: Vec<&str> = num_str.split(',').collect();
+    let after = smt::policy_cpus(&PolicyDir::collect_with_backend(CPU_FREQ_PATH, backend)?)?;
+    let changes = smt::describe_changes(&before, &after);
 
-            for part in parts {
-                let num = part.parse::<u32>()?;
-                policy_dir_numbers.push(num);
-            }
-        }
+    if changes.is_empty() {
+        println!("No policies changed.");
+    }
 
-        num_str => policy_dir_numbers.push(
-            num_str
-                .parse::<u32>()
-                .map_err(|_| ah::anyhow!("Invalid policy identifier provided."))?,
-        ),
+    for line in changes {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+pub fn op_uncore<B: CpufreqBackend>(
+    backend: &B,
+    args: &mut std::vec::IntoIter<String>,
+) -> ah::Result<()> {
+    let action = args
+        .next()
+        .ok_or(ah::anyhow!("No uncore action specified."))?
+        .to_lowercase();
+
+    if !matches!(action.as_str(), "get" | "set") {
+        return Err(unrecognized("uncore action", &action, &["get", "set"]));
+    }
+
+    let target = args.next().ok_or(ah::anyhow!("No packages specified."))?;
+
+    let target_value = match action.as_str() {
+        "get" => args
+            .next()
+            .ok_or(ah::anyhow!("No value specified."))?
+            .to_lowercase(),
+        _ => args
+            .next()
+            .ok_or(ah::anyhow!("No value specified to set."))?,
+    };
+
+    expect_end(args)?;
+
+    let packages: Option<Vec<u32>> = match target.as_str() {
+        "*" | "all" => None,
+        package_list => Some(cpulist::parse(package_list)?),
     };
 
-    let target_policy_dirs: Vec<PolicyDir> = policy_dirs
+    let uncore_dirs: Vec<UncoreDir<&B>> = UncoreDir::collect_with_backend(backend)?
         .into_iter()
-        .filter(|pd| policy_dir_numbers.contains(&pd.policy_number))
+        .filter(|ud| packages.as_ref().is_none_or(|p| p.contains(&ud.package)))
         .collect();
 
-    let mut output: Vec<String> = Vec::new();
+    if uncore_dirs.is_empty() {
+        ah::bail!("No uncore frequency domains matched '{}'.", target);
+    }
 
-    for (i, policy_dir) in target_policy_dirs.iter().enumerate() {
-        if i > 0 {
-            output.push("--------------------".to_string());
+    match action.as_str() {
+        "get" => {
+            for uncore_dir in uncore_dirs {
+                let name = format!("Package {} die {}", uncore_dir.package, uncore_dir.die);
+
+                match target_value.as_str() {
+                    "min" => println!(
+                        "{} uncore min frequency: {}",
+                        name,
+                        uncore_dir.read_min_freq()?
+                    ),
+                    "max" => println!(
+                        "{} uncore max frequency: {}",
+                        name,
+                        uncore_dir.read_max_freq()?
+                    ),
+                    "curr" | "current" => {
+                        println!(
+                            "{} uncore current frequency: {}",
+                            name,
+                            uncore_dir.read_current_freq()?
+                        )
+                    }
+                    "all" => println!(
+                        "{} uncore frequency: {} (min {}, max {}, limits {} to {})",
+                        name,
+                        uncore_dir.read_current_freq()?.to_ghz(),
+                        uncore_dir.read_min_freq()?.to_ghz(),
+                        uncore_dir.read_max_freq()?.to_ghz(),
+                        uncore_dir.read_initial_min_freq()?.to_ghz(),
+                        uncore_dir.read_initial_max_freq()?.to_ghz()
+                    ),
+                    value => {
+                        return Err(unrecognized(
+                            "uncore value",
+                            value,
+                            &["min", "max", "curr", "current", "all"],
+                        ))
+                    }
+                }
+            }
         }
 
-        match (
-            target_attrib.to_lowercase().as_str(),
-            target_value.to_lowercase().as_str(),
-        ) {
-            ("freq", "min") => {
-                let min_freq = policy_dir.read_scaling_min_freq()?;
+        _ => {
+            let FrequencyRange { min, max } = target_value
+                .parse::<FrequencyRange>()
+                .map_err(CpmError::from)?;
 
-                output.push(format!(
-                    "Policy {} scaling min frequency: {}",
-                    policy_dir.policy_number, min_freq
-                ));
+            for uncore_dir in uncore_dirs {
+                uncore_dir.set_freq_range(min.as_ref(), max.as_ref())?;
             }
+        }
+    }
 
-            ("freq", "max") => {
-                let max_freq = policy_dir.read_scaling_max_freq()?;
+    Ok(())
+}
 
-                output.push(format!(
-                    "Policy {} scaling max frequency: {}",
-                    policy_dir.policy_number, max_freq
-                ));
-            }
+pub fn op_profile<B: CpufreqBackend>(
+    backend: &B,
+    policy_dirs: Vec<PolicyDir<B>>,
+    args: &mut std::vec::IntoIter<String>,
+) -> ah::Result<()> {
+    let name = args.next().ok_or(ah::anyhow!("No profile specified."))?;
+    expect_end(args)?;
+
+    if name == "list" {
+        for profile in profiles::load_profiles(backend)? {
+            let settings: Vec<String> = profile
+                .settings
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect();
+
+            println!("{}: {}", profile.name, settings.join(" "));
+        }
 
-            ("freq", "current" | "curr") => {
-                let current_freq = policy_dir.read_current_freq()?;
+        return Ok(());
+    }
 
-                output.push(format!(
-                    "Policy {} current frequency: {}",
-                    policy_dir.policy_number, current_freq
-                ));
-            }
+    let profile = profiles::load_profile(backend, &name)?;
 
-            ("gov", "avail" | "available") => {
-                let available_govs = policy_dir.read_available_governors()?;
-                let mut govs = format!("Policy {} available governors...\n\n", policy_dir.policy_number);
+    // The firmware may reset frequency limits when its profile changes, so
+    // the platform profile goes first and everything else is applied on top.
+    if let Some(platform_profile) = profile.get("platform") {
+        platform::set_platform_profile(backend, platform_profile)?;
+    }
 
-                for (i, gov) in available_govs.iter().enumerate() {
-                    govs += &format!("{}.) - {}\n", i, gov);
-                }
+    let target_policy_dirs =
+        select_policies(policy_dirs, profile.get("policies").unwrap_or("all"))?;
 
-                output.push(govs);
-            }
+    for (key, value) in &profile.settings {
+        if key == "platform" || key == "policies" {
+            continue;
+        }
 
-            ("gov", "curr" | "current") => {
-                let current_gov = policy_dir.read_current_governor()?;
-                output.push(format!(
-                    "Policy {} current governor: {}",
-                    policy_dir.policy_number, current_gov
-                ));
+        for policy_dir in &target_policy_dirs {
+            for note in apply_attribute(policy_dir, key, value, None)? {
+                println!("{}", note);
             }
+        }
+    }
 
-            ("perf", "avail" | "available") => {
-                let available_profiles = policy_dir.read_available_perf_profiles()?;
-                let mut perfs = format!("Policy {} available performance profiles...\n\n", policy_dir.policy_number);
+    Ok(())
+}
 
-                for (i, perf) in available_profiles.iter().enumerate() {
-                    perfs += &format!("{}.) - {}\n", i, perf);
-                }
+pub fn op_info<B: CpufreqBackend>(
+    policy_dirs: Vec<PolicyDir<B>>,
+    args: &mut std::vec::IntoIter<String>,
+) -> ah::Result<()> {
+    let mut target_policy = String::from("all");
+    let mut json = false;
 
-                output.push(perfs);
+    let mut policy_given = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "--json" => json = true,
+            flag if flag.starts_with("--") => {
+                return Err(unrecognized("option", flag, &["--json"]))
+            }
+            _ if policy_given => ah::bail!("Unexpected argument '{}'.", arg),
+            policy => {
+                target_policy = policy.to_string();
+                policy_given = true;
             }
+        }
+    }
 
-            ("perf", "curr" | "current") => {
-                let current_perf = policy_dir.read_current_perf_profile()?;
-                output.push(format!(
-                    "Policy {} current performance profile: {}",
-                    policy_dir.policy_number, current_perf
-                ));
+    let target_policy_dirs = select_policies(policy_dirs, &target_policy)?;
+
+    if target_policy_dirs.is_empty() {
+        ah::bail!("No policies matched '{}'.", target_policy);
+    }
+
+    let output: Vec<String> = match json {
+        true => vec![format!(
+            "[{}]",
+            target_policy_dirs
+                .iter()
+                .map(format_info_json)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )],
+        false => target_policy_dirs.iter().map(format_info).collect(),
+    };
+
+    println!("{}", output.join("\n--------------------\n"));
+
+    Ok(())
+}
+
+/// Formats every attribute in the registry of a policy, one per line under
+/// its file name. Attributes the policy doesn't have are n/a.
+pub fn format_info<B: CpufreqBackend>(policy_dir: &PolicyDir<B>) -> String {
+    let name_width = ATTRIBUTES
+        .iter()
+        .map(|attribute| attribute.file_name.len())
+        .max()
+        .unwrap_or_default();
+
+    let mut lines = vec![format!("Policy {}\n", policy_dir.policy_number)];
+
+    for attribute in ATTRIBUTES {
+        let value = match policy_dir.read_value(attribute) {
+            Ok(value) => attribute.format(&value),
+            Err(_) => "n/a".to_string(),
+        };
+
+        lines.push(format!(
+            "    {:<width$}  {}",
+            attribute.file_name,
+            value,
+            width = name_width
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Formats every attribute in the registry of a policy as a JSON object,
+/// keyed by file name. Attributes the policy doesn't have are null.
+pub fn format_info_json<B: CpufreqBackend>(policy_dir: &PolicyDir<B>) -> String {
+    let values: Vec<String> = ATTRIBUTES
+        .iter()
+        .map(|attribute| {
+            let value = match policy_dir.read_value(attribute) {
+                Ok(value) => value.to_json(),
+                Err(_) => "null".to_string(),
+            };
+
+            format!(
+                "{}: {}",
+                attributes::json_string(attribute.file_name),
+                value
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"policy\": {}, \"attributes\": {{{}}}}}",
+        policy_dir.policy_number,
+        values.join(", ")
+    )
+}
+
+/// Formats everything explain found for a policy, ending with the verdict.
+fn format_explanation(explanation: &Explanation) -> String {
+    let mut lines = vec![format!(
+        "Policy {} is running at {} with the {} governor.\n",
+        explanation.policy,
+        explanation.current.to_ghz(),
+        explanation.governor.as_deref().unwrap_or("unknown")
+    )];
+
+    let name_width = explanation
+        .limits
+        .iter()
+        .map(|limit| limit.name.len())
+        .chain(Some(attributes::SCALING_MIN_FREQ.description.len()))
+        .max()
+        .unwrap_or_default();
+
+    lines.push("Limits, lowest first:".to_string());
+
+    for limit in &explanation.limits {
+        let cap = match &limit.detail {
+            Some(detail) => format!("{} ({})", limit.cap.to_ghz(), detail),
+            None => limit.cap.to_ghz().to_string(),
+        };
+
+        lines.push(format!(
+            "    {:<width$}  {}",
+            limit.name,
+            cap,
+            width = name_width
+        ));
+    }
+
+    lines.push(format!(
+        "    {:<width$}  {} (floor)",
+        attributes::SCALING_MIN_FREQ.description,
+        explanation.floor.to_ghz(),
+        width = name_width
+    ));
+
+    lines.push(format!(
+        "\nBoost: {}",
+        match explanation.boost {
+            Some(true) => "enabled",
+            Some(false) => "disabled",
+            None => "not controllable",
+        }
+    ));
+
+    lines.push("\nThermal throttle events:".to_string());
+
+    if explanation.throttling.is_empty() {
+        lines.push("    Not reported by these CPUs.".to_string());
+    }
+
+    for throttle in &explanation.throttling {
+        lines.push(format!(
+            "    CPU {:<4} core {} (+{} while sampling), package {} (+{})",
+            throttle.cpu,
+            throttle.core_count,
+            throttle.core_sampled,
+            throttle.package_count,
+            throttle.package_sampled
+        ));
+    }
+
+    if !explanation.power_limits.is_empty() {
+        lines.push("\nRAPL power limits:".to_string());
+
+        let watts = |uw: u64| format!("{:.1} W", uw as f64 / 1_000_000.0);
+
+        for limit in &explanation.power_limits {
+            lines.push(format!(
+                "    {} {:<12} {}, drawing {}",
+                limit.zone,
+                limit.constraint,
+                watts(limit.limit_uw),
+                limit.drawn_uw.map_or("n/a".to_string(), watts)
+            ));
+        }
+    }
+
+    lines.push(format!("\n{}", explanation.verdict()));
+    lines.join("\n")
+}
+
+pub fn op_explain<B: CpufreqBackend>(
+    backend: &B,
+    policy_dirs: Vec<PolicyDir<B>>,
+    args: &mut std::vec::IntoIter<String>,
+) -> ah::Result<()> {
+    let target_policy = args.next().ok_or(ah::anyhow!("No policies specified."))?;
+
+    let mut sample = Duration::from_millis(250);
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--sample" => {
+                let millis = args
+                    .next()
+                    .ok_or(ah::anyhow!("No duration specified for --sample."))?;
+
+                sample = Duration::from_millis(millis.parse::<u64>().map_err(|_| {
+                    ah::anyhow!(
+                        "Invalid sample duration '{}'. It must be a number of milliseconds.",
+                        millis
+                    )
+                })?);
+            }
+            flag if flag.starts_with("--") => {
+                return Err(unrecognized("option", flag, &["--sample"]))
             }
-            _ => (),
+            _ => ah::bail!("Unexpected argument '{}'.", arg),
         }
     }
 
-    for line in output {
-        println!("{}", line);
+    let target_policy_dirs = select_policies(policy_dirs, &target_policy)?;
+
+    if target_policy_dirs.is_empty() {
+        ah::bail!("No policies matched '{}'.", target_policy);
+    }
+
+    // The throttle and energy counters are shared by the policies, so they
+    // are sampled once for all of them.
+    let mut cpus: Vec<u32> = Vec::new();
+
+    for policy_dir in &target_policy_dirs {
+        cpus.extend(policy_dir.read_affected_cpus()?);
+    }
+
+    let sample = explain::Sample::take(backend, &cpus, sample);
+    let mut output: Vec<String> = Vec::new();
+
+    for policy_dir in &target_policy_dirs {
+        output.push(format_explanation(&explain::explain(policy_dir, &sample)?));
     }
 
+    println!("{}", output.join("\n--------------------\n"));
+
     Ok(())
 }
 
+/// Parses the command line and runs the command. --help, or -h, prints the
+/// full help text, or after a command, the help of that command instead of
+/// running it. Unknown commands and arguments are errors.
 pub fn parse_arguments() -> ah::Result<()> {
     let arguments: Vec<String> = env::args().collect();
     let mut arg_iter: std::vec::IntoIter<String> = arguments.into_iter();
-    let policy_dirs = PolicyDir::collect_from_dir(CPU_FREQ_PATH)?;
 
     // Ignore the first argument, since it's the path to the binary.
     arg_iter.next();
 
-    let first = arg_iter
-        .next()
-        .ok_or(ah::anyhow!("No arguments provided."))?;
+    let first = arg_iter.next().ok_or(ah::anyhow!("No command provided."))?;
 
-    match first.as_str() {
-        "set" => op_set(policy_dirs, &mut arg_iter)?,
-        "get" => op_get(policy_dirs, &mut arg_iter)?,
-        a => println!("Unrecognized: {}", a)
-    };
+    let is_help = |arg: &str| arg == "--help" || arg == "-h";
 
-    Ok(())
+    if is_help(&first) || first == "help" {
+        match arg_iter.next() {
+            Some(command) => {
+                let text = help::command(&command).ok_or_else(|| unrecognized_command(&command))?;
+                println!("{}", text);
+            }
+            None => println!("{}", help::full()),
+        }
+
+        return Ok(());
+    }
+
+    let help_text = help::command(&first).ok_or_else(|| unrecognized_command(&first))?;
+
+    if arg_iter.as_slice().iter().any(|arg| is_help(arg)) {
+        println!("{}", help_text);
+        return Ok(());
+    }
+
+    run_command(Sysfs, &first, &mut arg_iter)
+}
+
+/// Runs a command with the policies found under CPU_FREQ_PATH, reading and
+/// writing every file through the backend.
+pub fn run_command<B: CpufreqBackend + Clone>(
+    backend: B,
+    command: &str,
+    args: &mut std::vec::IntoIter<String>,
+) -> ah::Result<()> {
+    let policy_dirs = PolicyDir::collect_with_backend(CPU_FREQ_PATH, backend.clone())?;
+
+    match command {
+        "set" => op_set(&backend, policy_dirs, args),
+        "get" => op_get(&backend, policy_dirs, args),
+        "lock" => op_lock(&backend, policy_dirs, args),
+        "unlock" => op_unlock(&backend, policy_dirs, args),
+        "idle" => op_idle(&backend, policy_dirs, args),
+        "cpu" => op_cpu(&backend, args),
+        "smt" => op_smt(&backend, policy_dirs, args),
+        "uncore" => op_uncore(&backend, args),
+        "profile" => op_profile(&backend, policy_dirs, args),
+        "info" => op_info(policy_dirs, args),
+        "explain" => op_explain(&backend, policy_dirs, args),
+        command => Err(unrecognized_command(command)),
+    }
+}
+
+/// Returns an error for a command that isn't one, suggesting the closest.
+fn unrecognized_command(command: &str) -> ah::Error {
+    let commands: Vec<&str> = help::COMMANDS.iter().map(|(name, _)| *name).collect();
+    unrecognized("command", command, &commands)
 }
@@ -0,0 +1,402 @@
+use crate::{
+    error::{self, CpmError},
+    frequency::{parse_freq, Frequency},
+};
+
+/// How the contents of an attribute's file are parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// A single frequency in KHz.
+    Frequency,
+    /// A whitespace separated list, e.g. of governors or CPU numbers.
+    List,
+    /// A single word, e.g. a governor or driver name.
+    String,
+    /// A single integer.
+    Int,
+}
+
+/// The unit an integer attribute is reported in. Frequencies are always KHz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    None,
+    Nanoseconds,
+}
+
+/// How a value is checked before it's written.
+#[derive(Debug, Clone, Copy)]
+pub enum Validation {
+    None,
+    /// Must fall within cpuinfo_min_freq and cpuinfo_max_freq.
+    RatedRange,
+    /// Must be one of the entries listed by another attribute.
+    OneOf(&'static Attribute),
+    /// Must be one of the entries listed by another attribute, or an integer
+    /// from 0 up to the given maximum.
+    OneOfOrInt(&'static Attribute, i64),
+}
+
+/// A parsed attribute value.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Frequency(Frequency),
+    List(Vec<String>),
+    String(String),
+    Int(i64),
+}
+
+impl Value {
+    /// Returns the value as it's written to the attribute's file. Frequencies
+    /// that aren't a whole number of KHz can't be written, and are an error.
+    pub fn to_file_string(&self) -> error::Result<String> {
+        Ok(match self {
+            Value::Frequency(frequency) => frequency.as_whole_khz()?.to_string(),
+            Value::List(entries) => entries.join(" "),
+            Value::String(string) => string.clone(),
+            Value::Int(int) => int.to_string(),
+        })
+    }
+
+    /// Returns the value as JSON. Frequencies are numbers in KHz.
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Frequency(frequency) => frequency.as_khz().to_string(),
+            Value::List(entries) => format!(
+                "[{}]",
+                entries
+                    .iter()
+                    .map(|e| json_string(e))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::String(string) => json_string(string),
+            Value::Int(int) => int.to_string(),
+        }
+    }
+}
+
+/// Quotes and escapes a string for JSON output.
+pub fn json_string(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len() + 2);
+    escaped.push('"');
+
+    for c in string.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+/// Declares a single file in a policy directory, e.g. policy0/scaling_max_freq,
+/// along with everything needed to read, display, validate and write it.
+#[derive(Debug)]
+pub struct Attribute {
+    /// The file name in the policy directory.
+    pub file_name: &'static str,
+    /// The name used for the attribute on the command line, e.g. freq.max.
+    pub alias: &'static str,
+    /// A short description used when printing the value.
+    pub description: &'static str,
+    pub kind: Kind,
+    pub unit: Unit,
+    pub writable: bool,
+    pub validation: Validation,
+    /// Whether the attribute is saved by `cpm lock` and restored by unlock.
+    pub snapshot: bool,
+}
+
+impl Attribute {
+    /// Returns whether the name refers to this attribute, by file name or alias.
+    pub fn matches(&self, name: &str) -> bool {
+        name == self.file_name || name == self.alias
+    }
+
+    /// Parses the contents of the attribute's file.
+    pub fn parse(&self, content: &str) -> error::Result<Value> {
+        let content = content.trim();
+
+        Ok(match self.kind {
+            Kind::Frequency => Value::Frequency(Frequency::from_khz(
+                content.parse::<u64>().map_err(|_| {
+                    CpmError::Parse(format!(
+                        "Invalid frequency '{}' in {}.",
+                        content, self.file_name
+                    ))
+                })?,
+            )),
+            Kind::List => Value::List(content.split_whitespace().map(|s| s.to_string()).collect()),
+            Kind::String => Value::String(content.to_string()),
+            Kind::Int => Value::Int(content.parse::<i64>().map_err(|_| {
+                CpmError::Parse(format!(
+                    "Invalid integer '{}' in {}.",
+                    content, self.file_name
+                ))
+            })?),
+        })
+    }
+
+    /// Parses a value given on the command line. Frequencies accept every
+    /// format parse_freq does, and strings are lowercased.
+    pub fn parse_input(&self, input: &str) -> error::Result<Value> {
+        let input = input.trim();
+
+        Ok(match self.kind {
+            Kind::Frequency => Value::Frequency(parse_freq(input)?),
+            Kind::List => Value::List(input.split_whitespace().map(|s| s.to_string()).collect()),
+            Kind::String => Value::String(input.to_lowercase()),
+            Kind::Int => Value::Int(input.parse::<i64>().map_err(|_| {
+                CpmError::Parse(format!("Invalid integer '{}' for {}.", input, self.alias))
+            })?),
+        })
+    }
+
+    /// Formats a value for display, with its unit.
+    pub fn format(&self, value: &Value) -> String {
+        match (value, self.unit) {
+            (Value::Frequency(frequency), _) => frequency.to_ghz().to_string(),
+            (Value::Int(int), Unit::Nanoseconds) => format!("{} ns", int),
+            (Value::Int(int), _) => int.to_string(),
+            (Value::List(entries), _) => entries.join(" "),
+            (Value::String(string), _) => string.clone(),
+        }
+    }
+}
+
+pub static AFFECTED_CPUS: Attribute = Attribute {
+    file_name: "affected_cpus",
+    alias: "cpus",
+    description: "affected CPUs",
+    kind: Kind::List,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static RELATED_CPUS: Attribute = Attribute {
+    file_name: "related_cpus",
+    alias: "cpus.related",
+    description: "related CPUs",
+    kind: Kind::List,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static SCALING_DRIVER: Attribute = Attribute {
+    file_name: "scaling_driver",
+    alias: "driver",
+    description: "scaling driver",
+    kind: Kind::String,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static CPUINFO_TRANSITION_LATENCY: Attribute = Attribute {
+    file_name: "cpuinfo_transition_latency",
+    alias: "latency",
+    description: "transition latency",
+    kind: Kind::Int,
+    unit: Unit::Nanoseconds,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static BASE_FREQUENCY: Attribute = Attribute {
+    file_name: "base_frequency",
+    alias: "freq.base",
+    description: "base frequency",
+    kind: Kind::Frequency,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static CPUINFO_MIN_FREQ: Attribute = Attribute {
+    file_name: "cpuinfo_min_freq",
+    alias: "freq.rated_min",
+    description: "rated min frequency",
+    kind: Kind::Frequency,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static CPUINFO_MAX_FREQ: Attribute = Attribute {
+    file_name: "cpuinfo_max_freq",
+    alias: "freq.rated_max",
+    description: "rated max frequency",
+    kind: Kind::Frequency,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+/// The limit the firmware imposes through ACPI _PPC, only exposed by
+/// acpi-cpufreq. The kernel clamps scaling_max_freq to it.
+pub static BIOS_LIMIT: Attribute = Attribute {
+    file_name: "bios_limit",
+    alias: "freq.bios_limit",
+    description: "BIOS frequency limit",
+    kind: Kind::Frequency,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static SCALING_CUR_FREQ: Attribute = Attribute {
+    file_name: "scaling_cur_freq",
+    alias: "freq.curr",
+    description: "current frequency",
+    kind: Kind::Frequency,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static SCALING_AVAILABLE_FREQUENCIES: Attribute = Attribute {
+    file_name: "scaling_available_frequencies",
+    alias: "freq.avail",
+    description: "available frequencies",
+    kind: Kind::List,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static SCALING_AVAILABLE_GOVERNORS: Attribute = Attribute {
+    file_name: "scaling_available_governors",
+    alias: "gov.avail",
+    description: "available governors",
+    kind: Kind::List,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static SCALING_GOVERNOR: Attribute = Attribute {
+    file_name: "scaling_governor",
+    alias: "gov",
+    description: "current governor",
+    kind: Kind::String,
+    unit: Unit::None,
+    writable: true,
+    validation: Validation::OneOf(&SCALING_AVAILABLE_GOVERNORS),
+    snapshot: true,
+};
+
+pub static ENERGY_PERFORMANCE_AVAILABLE_PREFERENCES: Attribute = Attribute {
+    file_name: "energy_performance_available_preferences",
+    alias: "perf.avail",
+    description: "available performance profiles",
+    kind: Kind::List,
+    unit: Unit::None,
+    writable: false,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+pub static ENERGY_PERFORMANCE_PREFERENCE: Attribute = Attribute {
+    file_name: "energy_performance_preference",
+    alias: "perf",
+    description: "current performance profile",
+    kind: Kind::String,
+    unit: Unit::None,
+    writable: true,
+    validation: Validation::OneOfOrInt(&ENERGY_PERFORMANCE_AVAILABLE_PREFERENCES, 255),
+    snapshot: true,
+};
+
+pub static SCALING_MIN_FREQ: Attribute = Attribute {
+    file_name: "scaling_min_freq",
+    alias: "freq.min",
+    description: "scaling min frequency",
+    kind: Kind::Frequency,
+    unit: Unit::None,
+    writable: true,
+    validation: Validation::RatedRange,
+    snapshot: true,
+};
+
+pub static SCALING_MAX_FREQ: Attribute = Attribute {
+    file_name: "scaling_max_freq",
+    alias: "freq.max",
+    description: "scaling max frequency",
+    kind: Kind::Frequency,
+    unit: Unit::None,
+    writable: true,
+    validation: Validation::RatedRange,
+    snapshot: true,
+};
+
+pub static SCALING_SETSPEED: Attribute = Attribute {
+    file_name: "scaling_setspeed",
+    alias: "setspeed",
+    description: "userspace governor speed",
+    kind: Kind::String,
+    unit: Unit::None,
+    writable: true,
+    validation: Validation::None,
+    snapshot: false,
+};
+
+/// Every attribute cpm knows about. The governor comes before the performance
+/// profile because some drivers refuse profile changes under the performance
+/// governor, so writing attributes in this order is always safe, with the
+/// exception of scaling min and max, which need to be written as a pair.
+pub static ATTRIBUTES: [&Attribute; 17] = [
+    &AFFECTED_CPUS,
+    &RELATED_CPUS,
+    &SCALING_DRIVER,
+    &CPUINFO_TRANSITION_LATENCY,
+    &BASE_FREQUENCY,
+    &CPUINFO_MIN_FREQ,
+    &CPUINFO_MAX_FREQ,
+    &BIOS_LIMIT,
+    &SCALING_CUR_FREQ,
+    &SCALING_AVAILABLE_FREQUENCIES,
+    &SCALING_AVAILABLE_GOVERNORS,
+    &SCALING_GOVERNOR,
+    &ENERGY_PERFORMANCE_AVAILABLE_PREFERENCES,
+    &ENERGY_PERFORMANCE_PREFERENCE,
+    &SCALING_MIN_FREQ,
+    &SCALING_MAX_FREQ,
+    &SCALING_SETSPEED,
+];
+
+/// Finds an attribute by file name or alias. The words current and available
+/// may be abbreviated, and a trailing .curr is optional, so gov, gov.curr and
+/// scaling_governor all resolve to the same attribute.
+pub fn find(name: &str) -> Option<&'static Attribute> {
+    let find_exact = |name: &str| ATTRIBUTES.iter().copied().find(|a| a.matches(name));
+
+    let name = name.to_lowercase();
+
+    if let Some(attribute) = find_exact(&name) {
+        return Some(attribute);
+    }
+
+    let alias = name
+        .replace("current", "curr")
+        .replace("available", "avail");
+
+    find_exact(&alias).or_else(|| find_exact(alias.strip_suffix(".curr")?))
+}
@@ -0,0 +1,339 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Access to the files of the cpufreq sysfs interface. PolicyDir and the
+/// commands built on it go through a backend instead of std::fs, so that they
+/// can run against an in-memory tree, or record writes instead of making them.
+pub trait CpufreqBackend {
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()>;
+
+    fn is_file(&self, path: &Path) -> bool;
+
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Returns the paths of the entries in the directory, files and
+    /// directories alike, in no particular order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+
+    /// Returns whether the file's mode bits allow writing. Root can write to
+    /// files regardless, but sysfs marks the files it rejects writes to as read only.
+    fn is_writable(&self, path: &Path) -> bool;
+
+    /// Creates the file and any missing parent directories, or replaces the
+    /// file's contents. sysfs files can't be created, this is for cpm's own
+    /// state, such as the lock state.
+    fn create(&self, path: &Path, contents: &str) -> io::Result<()>;
+
+    fn remove(&self, path: &Path) -> io::Result<()>;
+}
+
+impl<B: CpufreqBackend + ?Sized> CpufreqBackend for &B {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        (**self).read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        (**self).write(path, contents)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        (**self).is_file(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        (**self).is_dir(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        (**self).read_dir(path)
+    }
+
+    fn is_writable(&self, path: &Path) -> bool {
+        (**self).is_writable(path)
+    }
+
+    fn create(&self, path: &Path, contents: &str) -> io::Result<()> {
+        (**self).create(path, contents)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        (**self).remove(path)
+    }
+}
+
+/// The real sysfs, through std::fs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sysfs;
+
+impl CpufreqBackend for Sysfs {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        fs::write(path, contents)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(path)?.map(|entry| Ok(entry?.path())).collect()
+    }
+
+    fn is_writable(&self, path: &Path) -> bool {
+        fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o222 != 0)
+    }
+
+    fn create(&self, path: &Path, contents: &str) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, contents)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct MemoryFile {
+    contents: String,
+    writable: bool,
+}
+
+/// A file tree held in memory, for running commands without a real machine.
+/// Directories aren't stored, they exist as long as a file exists below them.
+#[derive(Debug, Default)]
+pub struct InMemory {
+    files: Mutex<BTreeMap<PathBuf, MemoryFile>>,
+}
+
+impl InMemory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a writable file, or replaces the contents of an existing one.
+    pub fn insert(&self, path: impl Into<PathBuf>, contents: &str) {
+        self.insert_file(path.into(), contents, true);
+    }
+
+    /// Adds a file that rejects writes, like the cpuinfo_* files in sysfs.
+    pub fn insert_read_only(&self, path: impl Into<PathBuf>, contents: &str) {
+        self.insert_file(path.into(), contents, false);
+    }
+
+    /// Removes a file, returning whether it existed.
+    pub fn remove(&self, path: &Path) -> bool {
+        self.files.lock().unwrap().remove(path).is_some()
+    }
+
+    /// Returns the contents of a file, if it exists.
+    pub fn contents(&self, path: &Path) -> Option<String> {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .map(|f| f.contents.clone())
+    }
+
+    fn insert_file(&self, path: PathBuf, contents: &str, writable: bool) {
+        self.files.lock().unwrap().insert(
+            path,
+            MemoryFile {
+                contents: contents.to_string(),
+                writable,
+            },
+        );
+    }
+}
+
+impl CpufreqBackend for InMemory {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.contents(path).ok_or(ErrorKind::NotFound.into())
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        let file = files
+            .get_mut(path)
+            .ok_or(io::Error::from(ErrorKind::NotFound))?;
+
+        if !file.writable {
+            return Err(ErrorKind::PermissionDenied.into());
+        }
+
+        file.contents = contents.trim_end_matches('\n').to_string();
+        Ok(())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.lock().unwrap().contains_key(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files
+            .lock()
+            .unwrap()
+            .keys()
+            .any(|file| file != path && file.starts_with(path))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        if !self.is_dir(path) {
+            return Err(ErrorKind::NotFound.into());
+        }
+
+        let entries: BTreeSet<PathBuf> = self
+            .files
+            .lock()
+            .unwrap()
+            .keys()
+            .filter_map(|file| file.strip_prefix(path).ok()?.components().next())
+            .map(|entry| path.join(entry))
+            .collect();
+
+        Ok(entries.into_iter().collect())
+    }
+
+    fn is_writable(&self, path: &Path) -> bool {
+        self.files
+            .lock()
+            .unwrap()
+            .get(path)
+            .is_some_and(|f| f.writable)
+    }
+
+    fn create(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.insert(path, contents);
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        match InMemory::remove(self, path) {
+            true => Ok(()),
+            false => Err(ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+/// Wraps another backend for dry runs. Reads go to the inner backend, and
+/// writes are checked against it and recorded, but never made. Reading a file
+/// that was written returns the recorded contents, so commands that read back
+/// what they wrote behave as if the writes happened. Created files are
+/// recorded as writes, and removed files are hidden, without either reaching
+/// the inner backend.
+#[derive(Debug, Default)]
+pub struct Recording<B: CpufreqBackend> {
+    inner: B,
+    writes: Mutex<Vec<(PathBuf, String)>>,
+    removed: Mutex<BTreeSet<PathBuf>>,
+}
+
+impl<B: CpufreqBackend> Recording<B> {
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            writes: Mutex::new(Vec::new()),
+            removed: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Returns every recorded write, in the order it was made.
+    pub fn writes(&self) -> Vec<(PathBuf, String)> {
+        self.writes.lock().unwrap().clone()
+    }
+}
+
+impl<B: CpufreqBackend> CpufreqBackend for Recording<B> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        if self.removed.lock().unwrap().contains(path) {
+            return Err(ErrorKind::NotFound.into());
+        }
+
+        let written = self
+            .writes
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|(written, _)| written == path)
+            .map(|(_, contents)| contents.clone());
+
+        match written {
+            Some(contents) => Ok(contents),
+            None => self.inner.read(path),
+        }
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        if !self.inner.is_file(path) {
+            return Err(ErrorKind::NotFound.into());
+        }
+
+        if !self.inner.is_writable(path) {
+            return Err(ErrorKind::PermissionDenied.into());
+        }
+
+        self.writes
+            .lock()
+            .unwrap()
+            .push((path.to_path_buf(), contents.to_string()));
+
+        Ok(())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        if self.removed.lock().unwrap().contains(path) {
+            return false;
+        }
+
+        self.inner.is_file(path) || self.writes.lock().unwrap().iter().any(|(p, _)| p == path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.inner.is_dir(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.inner.read_dir(path)
+    }
+
+    fn is_writable(&self, path: &Path) -> bool {
+        self.inner.is_writable(path)
+    }
+
+    fn create(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.removed.lock().unwrap().remove(path);
+
+        self.writes
+            .lock()
+            .unwrap()
+            .push((path.to_path_buf(), contents.to_string()));
+
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        if !self.is_file(path) {
+            return Err(ErrorKind::NotFound.into());
+        }
+
+        self.removed.lock().unwrap().insert(path.to_path_buf());
+        Ok(())
+    }
+}
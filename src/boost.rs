@@ -0,0 +1,61 @@
+use crate::{
+    backend::CpufreqBackend,
+    error::{self, CpmError},
+    BOOST_PATH, NO_TURBO_PATH,
+};
+
+use std::path::Path;
+
+/// Returns the switch frequency boosting (turbo) is controlled through. The
+/// generic cpufreq boost switch is preferred, falling back to intel_pstate's
+/// no_turbo, whose meaning is inverted. Returns None when neither is present.
+pub fn boost_path<B: CpufreqBackend>(backend: &B) -> Option<&'static str> {
+    [BOOST_PATH, NO_TURBO_PATH]
+        .into_iter()
+        .find(|path| backend.is_file(Path::new(path)))
+}
+
+/// Returns whether frequency boosting is enabled, read from boost_path.
+/// Returns None when boosting can't be controlled.
+pub fn read_boost<B: CpufreqBackend>(backend: &B) -> error::Result<Option<bool>> {
+    let Some(path) = boost_path(backend) else {
+        return Ok(None);
+    };
+
+    let flag = read_flag(backend, path)?;
+    Ok(Some(if path == NO_TURBO_PATH { !flag } else { flag }))
+}
+
+/// Enables or disables frequency boosting through whichever switch the
+/// running driver exposes.
+pub fn set_boost<B: CpufreqBackend>(backend: &B, enabled: bool) -> error::Result<()> {
+    match boost_path(backend) {
+        Some(NO_TURBO_PATH) => write_flag(backend, NO_TURBO_PATH, !enabled),
+        Some(path) => write_flag(backend, path, enabled),
+        None => Err(CpmError::Unsupported(
+            "Frequency boosting can't be controlled on this system.".to_string(),
+        )),
+    }
+}
+
+fn read_flag<B: CpufreqBackend>(backend: &B, path: &str) -> error::Result<bool> {
+    let path = Path::new(path);
+    let content = backend.read(path).map_err(|e| CpmError::from_io(path, e))?;
+
+    match content.trim() {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        other => Err(CpmError::Parse(format!(
+            "Unexpected value '{}' in '{}'.",
+            other,
+            path.display()
+        ))),
+    }
+}
+
+fn write_flag<B: CpufreqBackend>(backend: &B, path: &str, value: bool) -> error::Result<()> {
+    let path = Path::new(path);
+    backend
+        .write(path, if value { "1" } else { "0" })
+        .map_err(|e| CpmError::from_io(path, e))
+}
@@ -0,0 +1,156 @@
+use crate::{
+    backend::CpufreqBackend,
+    error::{self, CpmError},
+    CPU_PATH,
+};
+
+use std::path::{Path, PathBuf};
+
+/// A single cpuidle state of a CPU, e.g. cpu0/cpuidle/state2, read from the
+/// files in its state directory. Latency, residency and time are in
+/// microseconds, as reported by the kernel.
+#[derive(Debug, Clone)]
+pub struct IdleState {
+    pub cpu: u32,
+    pub index: u32,
+    pub full_path: PathBuf,
+    pub name: String,
+    pub desc: String,
+    pub latency: u64,
+    pub residency: u64,
+    pub usage: u64,
+    pub time: u64,
+    pub disabled: bool,
+}
+
+impl IdleState {
+    /// Reads every file of the state directory at the given path.
+    pub fn from<B: CpufreqBackend>(
+        backend: &B,
+        cpu: u32,
+        index: u32,
+        path: &Path,
+    ) -> error::Result<Self> {
+        let read = |attribute: &str| read_attribute(backend, path, attribute);
+        let read_number = |attribute: &str| {
+            let content = read(attribute)?;
+
+            content.parse::<u64>().map_err(|_| {
+                CpmError::Parse(format!(
+                    "Invalid number '{}' in '{}'.",
+                    content,
+                    path.join(attribute).display()
+                ))
+            })
+        };
+
+        Ok(Self {
+            cpu,
+            index,
+            full_path: path.to_path_buf(),
+            name: read("name")?,
+            desc: read("desc")?,
+            latency: read_number("latency")?,
+            residency: read_number("residency")?,
+            usage: read_number("usage")?,
+            time: read_number("time")?,
+            disabled: read("disable")? == "1",
+        })
+    }
+
+    /// Returns the state directory name, e.g. state2.
+    pub fn dir_name(&self) -> String {
+        format!("state{}", self.index)
+    }
+
+    /// Returns whether the selector names this state. A selector can be the
+    /// state index (2), the directory name (state2), or the state name (C1E),
+    /// which is compared case insensitively.
+    pub fn matches(&self, selector: &str) -> bool {
+        selector == self.index.to_string()
+            || selector == self.dir_name()
+            || selector.eq_ignore_ascii_case(&self.name)
+    }
+
+    /// Writes the disable file of the state, preventing or allowing the CPU
+    /// from entering it.
+    pub fn set_disabled<B: CpufreqBackend>(
+        &self,
+        backend: &B,
+        disabled: bool,
+    ) -> error::Result<()> {
+        write_disable(
+            backend,
+            self.cpu,
+            &self.dir_name(),
+            if disabled { "1" } else { "0" },
+        )
+    }
+}
+
+/// Returns the path of the cpuidle directory of the CPU.
+pub fn cpuidle_path(cpu: u32) -> PathBuf {
+    Path::new(CPU_PATH)
+        .join(format!("cpu{}", cpu))
+        .join("cpuidle")
+}
+
+/// Reads every idle state of the CPU, ordered by index. CPUs without a
+/// cpuidle directory have no idle states, rather than being an error.
+pub fn read_idle_states<B: CpufreqBackend>(backend: &B, cpu: u32) -> error::Result<Vec<IdleState>> {
+    let path = cpuidle_path(cpu);
+
+    if !backend.is_dir(&path) {
+        return Ok(Vec::new());
+    }
+
+    let mut states: Vec<IdleState> = Vec::new();
+
+    for entry_path in backend
+        .read_dir(&path)
+        .map_err(|e| CpmError::from_io(&path, e))?
+    {
+        let name = entry_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+
+        let Some(Ok(index)) = name.strip_prefix("state").map(|n| n.parse::<u32>()) else {
+            continue;
+        };
+
+        states.push(IdleState::from(backend, cpu, index, &entry_path)?);
+    }
+
+    states.sort_by_key(|state| state.index);
+
+    Ok(states)
+}
+
+/// Writes the raw disable value of a state directory, e.g. state2, of the CPU.
+pub fn write_disable<B: CpufreqBackend>(
+    backend: &B,
+    cpu: u32,
+    state: &str,
+    value: &str,
+) -> error::Result<()> {
+    let path = cpuidle_path(cpu).join(state).join("disable");
+
+    backend
+        .write(&path, value)
+        .map_err(|e| CpmError::from_io(&path, e))
+}
+
+fn read_attribute<B: CpufreqBackend>(
+    backend: &B,
+    state_path: &Path,
+    attribute: &str,
+) -> error::Result<String> {
+    let path = state_path.join(attribute);
+
+    backend
+        .read(&path)
+        .map(|content| content.trim().to_string())
+        .map_err(|e| CpmError::from_io(&path, e))
+}
@@ -0,0 +1,43 @@
+use crate::error::{self, CpmError};
+
+/// Parses a list of numbers, where each comma separated part is either a
+/// single number or an inclusive range written as 0:4 or 0-4. This covers both
+/// policy identifiers and the kernel's cpulist format, e.g. 0-3,6,8:9. An
+/// empty or blank list, as the kernel writes for no CPUs, parses as empty.
+pub fn parse(num_str: &str) -> error::Result<Vec<u32>> {
+    let num_str = num_str.trim();
+    let mut numbers: Vec<u32> = Vec::new();
+
+    if num_str.is_empty() {
+        return Ok(numbers);
+    }
+
+    for part in num_str.split(',') {
+        match part.split_once([':', '-']) {
+            Some((start, end)) => {
+                let start = start.parse::<u32>().map_err(|_| {
+                    CpmError::Parse(format!("Invalid range start '{}' in '{}'.", start, num_str))
+                })?;
+
+                let end = end.parse::<u32>().map_err(|_| {
+                    CpmError::Parse(format!("Invalid range end '{}' in '{}'.", end, num_str))
+                })?;
+
+                if end < start {
+                    return Err(CpmError::Parse(format!(
+                        "The end of range '{}' must be greater than its start.",
+                        part
+                    )));
+                }
+
+                numbers.extend(start..=end);
+            }
+
+            None => numbers.push(part.parse::<u32>().map_err(|_| {
+                CpmError::Parse(format!("Invalid number '{}' in '{}'.", part, num_str))
+            })?),
+        }
+    }
+
+    Ok(numbers)
+}
@@ -0,0 +1,94 @@
+use crate::{
+    backend::CpufreqBackend,
+    error::{self, CpmError},
+    suggest, CPU_PATH,
+};
+
+use std::path::{Path, PathBuf};
+
+/// The named Energy Performance Bias values the kernel understands, mapped to
+/// the numbers it stores for them. The full range is 0 (performance) to 15
+/// (power).
+pub const EPB_NAMES: [(&str, u8); 5] = [
+    ("performance", 0),
+    ("balance-performance", 4),
+    ("normal", 6),
+    ("balance-power", 8),
+    ("power", 15),
+];
+
+pub const EPB_MAX: u8 = 15;
+
+/// Returns the path of the energy_perf_bias file of the CPU.
+pub fn epb_path(cpu: u32) -> PathBuf {
+    Path::new(CPU_PATH)
+        .join(format!("cpu{}", cpu))
+        .join("power")
+        .join("energy_perf_bias")
+}
+
+/// Parses an EPB value, either a number from 0 to 15 or one of EPB_NAMES.
+/// Underscores are accepted in place of dashes, matching EPP's spelling.
+pub fn parse_epb(value: &str) -> error::Result<u8> {
+    let value = value.to_lowercase().replace('_', "-");
+
+    if let Some((_, bias)) = EPB_NAMES.iter().find(|(name, _)| *name == value) {
+        return Ok(*bias);
+    }
+
+    match value.parse::<u8>() {
+        Ok(bias) if bias <= EPB_MAX => Ok(bias),
+        _ => Err(CpmError::Parse(format!(
+            "Invalid energy performance bias '{}'.{} Must be a number from 0 to {}, or one of: {}",
+            value,
+            suggest::did_you_mean(&value, EPB_NAMES.map(|(name, _)| name)),
+            EPB_MAX,
+            EPB_NAMES.map(|(name, _)| name).join(", ")
+        ))),
+    }
+}
+
+/// Returns the name of an EPB value, if it has one.
+pub fn epb_name(bias: u8) -> Option<&'static str> {
+    EPB_NAMES
+        .iter()
+        .find(|(_, b)| *b == bias)
+        .map(|(name, _)| *name)
+}
+
+pub fn read_epb<B: CpufreqBackend>(backend: &B, cpu: u32) -> error::Result<u8> {
+    let path = epb_path(cpu);
+
+    if !backend.is_file(&path) {
+        return Err(CpmError::Unsupported(format!(
+            "CPU {} doesn't support energy performance bias.",
+            cpu
+        )));
+    }
+
+    let content = backend
+        .read(&path)
+        .map_err(|e| CpmError::from_io(&path, e))?;
+
+    content.trim().parse::<u8>().map_err(|_| {
+        CpmError::Parse(format!(
+            "Invalid energy performance bias in '{}'.",
+            path.display()
+        ))
+    })
+}
+
+pub fn set_epb<B: CpufreqBackend>(backend: &B, cpu: u32, bias: u8) -> error::Result<()> {
+    let path = epb_path(cpu);
+
+    if !backend.is_file(&path) {
+        return Err(CpmError::Unsupported(format!(
+            "CPU {} doesn't support energy performance bias.",
+            cpu
+        )));
+    }
+
+    backend
+        .write(&path, &bias.to_string())
+        .map_err(|e| CpmError::from_io(&path, e))
+}
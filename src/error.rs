@@ -0,0 +1,146 @@
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// Errors returned by every module of the library, so that consumers can
+/// tell failures apart without matching on message strings.
+#[derive(Debug)]
+pub enum CpmError {
+    /// A file or directory that was expected to exist doesn't.
+    NotFound { path: PathBuf },
+    /// The file exists, but the process isn't allowed to read or write it.
+    PermissionDenied { path: PathBuf },
+    /// A value falls outside of the range its target accepts. Frequencies are
+    /// given in KHz, and `what` says which value it was.
+    OutOfRange {
+        what: String,
+        requested: u64,
+        min: u64,
+        max: u64,
+    },
+    /// The system, driver or policy doesn't support the requested value or
+    /// feature, e.g. a governor that isn't available.
+    Unsupported(String),
+    /// User input or the contents of a file couldn't be parsed.
+    Parse(String),
+    /// The policy's CPUs are all offline, so the kernel refuses access to it.
+    Inactive { policy: u32 },
+    /// Any other I/O error.
+    Io { path: PathBuf, source: io::Error },
+    /// The write succeeded, but the file reads back as something else, because
+    /// the kernel clamped or ignored the value, e.g. a max frequency above the
+    /// bios_limit, or EPP under the performance governor.
+    NotApplied {
+        path: PathBuf,
+        written: String,
+        actual: String,
+    },
+    /// A change failed part way, and writing back the state from before it
+    /// failed as well, e.g. when `cpm lock` can't apply or undo its settings.
+    RestoreFailed {
+        error: Box<CpmError>,
+        restore_error: Box<CpmError>,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, CpmError>;
+
+impl CpmError {
+    /// Converts an I/O error on the given path into the matching variant.
+    pub fn from_io(path: &Path, error: io::Error) -> Self {
+        match error.kind() {
+            ErrorKind::NotFound => CpmError::NotFound {
+                path: path.to_path_buf(),
+            },
+            ErrorKind::PermissionDenied => CpmError::PermissionDenied {
+                path: path.to_path_buf(),
+            },
+            _ => CpmError::Io {
+                path: path.to_path_buf(),
+                source: error,
+            },
+        }
+    }
+
+    /// The process exit code the CLI uses for this error. Errors that aren't
+    /// a CpmError exit with 1.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CpmError::NotFound { .. } => 2,
+            CpmError::PermissionDenied { .. } => 3,
+            CpmError::OutOfRange { .. } => 4,
+            CpmError::Unsupported(_) => 5,
+            CpmError::Parse(_) => 6,
+            CpmError::Inactive { .. } => 7,
+            CpmError::Io { .. } => 8,
+            CpmError::NotApplied { .. } => 9,
+            CpmError::RestoreFailed { error, .. } => error.exit_code(),
+        }
+    }
+}
+
+impl fmt::Display for CpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CpmError::NotFound { path } => {
+                write!(f, "The file '{}' doesn't exist.", path.display())
+            }
+            CpmError::PermissionDenied { path } => write!(
+                f,
+                "Permission denied for '{}'. Changing CPU settings usually requires root.",
+                path.display()
+            ),
+            CpmError::OutOfRange {
+                what,
+                requested,
+                min,
+                max,
+            } => write!(
+                f,
+                "The {} '{}' is out of range '{}' to '{}'.",
+                what, requested, min, max
+            ),
+            CpmError::Unsupported(message) => write!(f, "{}", message),
+            CpmError::Parse(message) => write!(f, "{}", message),
+            CpmError::Inactive { policy } => write!(
+                f,
+                "Policy {} is inactive because all of its CPUs are offline.",
+                policy
+            ),
+            CpmError::Io { path, source } => write!(
+                f,
+                "Couldn't access '{}' due to error '{}'",
+                path.display(),
+                source
+            ),
+            CpmError::NotApplied {
+                path,
+                written,
+                actual,
+            } => write!(
+                f,
+                "Wrote '{}' to '{}', but the kernel kept '{}' instead.",
+                written,
+                path.display(),
+                actual
+            ),
+            CpmError::RestoreFailed {
+                error,
+                restore_error,
+            } => write!(
+                f,
+                "{}\nRestoring the prior state also failed: {}\nRun 'cpm unlock' to retry.",
+                error, restore_error
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CpmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CpmError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
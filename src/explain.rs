@@ -0,0 +1,438 @@
+use crate::{
+    attributes::{self, Attribute},
+    backend::CpufreqBackend,
+    boost, error,
+    frequency::Frequency,
+    hotplug,
+    policies::PolicyDir,
+    CPU_PATH, INTEL_PSTATE_PATH, POWERCAP_PATH,
+};
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// How close the current frequency has to be to a limit, in percent of the
+/// limit, to count as running at it. scaling_cur_freq is only a sample, and
+/// rarely lands exactly on the limit.
+const AT_LIMIT_PERCENT: u64 = 2;
+
+/// How close a RAPL zone has to draw to its power limit, in percent of the
+/// limit, to count as being held back by it.
+const AT_POWER_LIMIT_PERCENT: u64 = 95;
+
+/// A cap on how fast a policy may run, and the file it comes from.
+#[derive(Debug, Clone)]
+pub struct Limit {
+    /// What imposes the limit, e.g. BIOS frequency limit.
+    pub name: String,
+    pub path: PathBuf,
+    pub cap: Frequency,
+    /// How the cap was worked out, when it isn't read directly.
+    pub detail: Option<String>,
+}
+
+/// The thermal throttle counters of a CPU, from cpuN/thermal_throttle.
+#[derive(Debug, Clone)]
+pub struct ThermalThrottle {
+    pub cpu: u32,
+    /// The package of the CPU, whose package counter every CPU of the
+    /// package reports.
+    pub package: Option<u32>,
+    pub core_count: u64,
+    pub package_count: u64,
+    /// The core events counted while sampling.
+    pub core_sampled: u64,
+    /// The package events counted while sampling.
+    pub package_sampled: u64,
+}
+
+/// A RAPL power limit of a powercap zone, e.g. the long_term limit of
+/// package-0, along with the power the zone drew while sampling.
+#[derive(Debug, Clone)]
+pub struct PowerLimit {
+    pub zone: String,
+    pub constraint: String,
+    pub limit_uw: u64,
+    /// The average power drawn while sampling. energy_uj is usually only
+    /// readable by root, so this is None without it.
+    pub drawn_uw: Option<u64>,
+}
+
+/// What's holding a policy at its current frequency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cause {
+    /// Running at the lowest limit, which every named limit caps it at.
+    Limit(Vec<String>),
+    /// Below its limits, with its CPUs thermally throttling while sampling.
+    Thermal,
+    /// Below its limits, with a zone drawing close to its RAPL power limit.
+    Power { zone: String, constraint: String },
+    /// At scaling_min_freq, where the governor put it.
+    Floor,
+    /// Between the limits, where the governor put it.
+    Governor,
+}
+
+/// The thermal throttle counters of a set of CPUs and the power drawn by
+/// every RAPL zone, sampled once and shared by every policy explained.
+#[derive(Debug, Clone, Default)]
+pub struct Sample {
+    pub duration: Duration,
+    pub throttling: Vec<ThermalThrottle>,
+    pub power_limits: Vec<PowerLimit>,
+}
+
+impl Sample {
+    /// Reads the thermal throttle counters of the CPUs and the RAPL energy
+    /// counters, waits for the duration, and reads them again. A zero
+    /// duration only reads the counters, and measures nothing.
+    pub fn take<B: CpufreqBackend>(backend: &B, cpus: &[u32], duration: Duration) -> Self {
+        let throttle_before: Vec<(u32, u64, u64)> = cpus
+            .iter()
+            .filter_map(|cpu| {
+                read_throttle_counts(backend, *cpu).map(|(core, package)| (*cpu, core, package))
+            })
+            .collect();
+
+        let zones = read_power_zones(backend);
+        let energy_before: Vec<Option<u64>> = zones
+            .iter()
+            .map(|zone| read_u64(backend, &zone.join("energy_uj")))
+            .collect();
+
+        if !duration.is_zero() {
+            thread::sleep(duration);
+        }
+
+        let throttling: Vec<ThermalThrottle> = throttle_before
+            .into_iter()
+            .map(|(cpu, core_before, package_before)| {
+                let (core_count, package_count) =
+                    read_throttle_counts(backend, cpu).unwrap_or((core_before, package_before));
+
+                ThermalThrottle {
+                    cpu,
+                    package: hotplug::read_package_id(backend, cpu).ok(),
+                    core_count,
+                    package_count,
+                    core_sampled: core_count.saturating_sub(core_before),
+                    package_sampled: package_count.saturating_sub(package_before),
+                }
+            })
+            .collect();
+
+        let mut power_limits = Vec::new();
+
+        for (zone, energy_before) in zones.iter().zip(energy_before) {
+            let drawn_uw = match (energy_before, read_u64(backend, &zone.join("energy_uj"))) {
+                (Some(before), Some(after)) if !duration.is_zero() => {
+                    // The counter wraps around at max_energy_range_uj.
+                    let energy_uj = match after >= before {
+                        true => Some(after - before),
+                        false => read_u64(backend, &zone.join("max_energy_range_uj"))
+                            .map(|range| range.saturating_sub(before) + after),
+                    };
+
+                    energy_uj.map(|energy_uj| {
+                        (energy_uj as u128 * 1_000_000 / duration.as_micros()) as u64
+                    })
+                }
+                _ => None,
+            };
+
+            power_limits.extend(read_power_limits(backend, zone, drawn_uw));
+        }
+
+        Self {
+            duration,
+            throttling,
+            power_limits,
+        }
+    }
+}
+
+/// Every limit that could be in play for a policy, and which of them, if
+/// any, is keeping it at its current frequency.
+#[derive(Debug, Clone)]
+pub struct Explanation {
+    pub policy: u32,
+    pub current: Frequency,
+    pub governor: Option<String>,
+    /// The scaling min frequency, below which the policy never runs.
+    pub floor: Frequency,
+    /// Whether boost is enabled, or None if it can't be controlled.
+    pub boost: Option<bool>,
+    /// The caps on the frequency, lowest first.
+    pub limits: Vec<Limit>,
+    /// The throttle counters of the CPUs that report them.
+    pub throttling: Vec<ThermalThrottle>,
+    pub power_limits: Vec<PowerLimit>,
+    pub cause: Cause,
+}
+
+impl Explanation {
+    /// Returns the thermal throttle events counted while sampling. Every CPU
+    /// of a package reports the same package counter, so the package events
+    /// are counted once per package.
+    pub fn throttle_events(&self) -> u64 {
+        let mut packages = BTreeSet::new();
+
+        self.throttling
+            .iter()
+            .map(|t| match packages.insert(t.package) {
+                true => t.core_sampled + t.package_sampled,
+                false => t.core_sampled,
+            })
+            .sum()
+    }
+
+    /// Returns a sentence stating what's holding the policy at its frequency.
+    pub fn verdict(&self) -> String {
+        let governor = self.governor.as_deref().unwrap_or("current");
+
+        match &self.cause {
+            Cause::Limit(names) => format!(
+                "Policy {} is capped at {} by its {}.",
+                self.policy,
+                self.limits.first().map_or(self.current, |limit| limit.cap).to_ghz(),
+                names.join(" and its ")
+            ),
+            Cause::Thermal => format!(
+                "Policy {} is below its limits because its CPUs are thermally throttling, {} events were counted while sampling.",
+                self.policy,
+                self.throttle_events()
+            ),
+            Cause::Power { zone, constraint } => format!(
+                "Policy {} is below its limits because {} is drawing close to its {} RAPL power limit.",
+                self.policy, zone, constraint
+            ),
+            Cause::Floor => format!(
+                "Policy {} is at its scaling min frequency, so no limit is holding it back. The {} governor chose the lowest frequency, usually because the CPUs are idle.",
+                self.policy, governor
+            ),
+            Cause::Governor => format!(
+                "Policy {} is below every limit, so no limit is holding it back. The {} governor chose {} for the current load.",
+                self.policy,
+                governor,
+                self.current.to_ghz()
+            ),
+        }
+    }
+}
+
+/// Gathers every limit on the policy's frequency: scaling min and max, the
+/// rated limits, bios_limit, intel_pstate's max_perf_pct and boost. Then
+/// works out which of them, or the thermal throttling and RAPL power draw of
+/// the sample, is holding it at its current frequency. The sample should
+/// cover the policy's CPUs. Without a sample duration, only the frequency
+/// limits can be named as the cause.
+pub fn explain<B: CpufreqBackend>(
+    policy_dir: &PolicyDir<B>,
+    sample: &Sample,
+) -> error::Result<Explanation> {
+    let backend = &policy_dir.backend;
+    let rated_max = policy_dir.read_rated_max_freq()?;
+
+    let policy_limit = |attribute: &Attribute, cap: Frequency| Limit {
+        name: attribute.description.to_string(),
+        path: policy_dir.full_path.join(attribute.file_name),
+        cap,
+        detail: None,
+    };
+
+    let mut limits = vec![
+        policy_limit(
+            &attributes::SCALING_MAX_FREQ,
+            policy_dir.read_scaling_max_freq()?,
+        ),
+        policy_limit(&attributes::CPUINFO_MAX_FREQ, rated_max),
+    ];
+
+    if policy_dir.has_file(&attributes::BIOS_LIMIT) {
+        limits.push(policy_limit(
+            &attributes::BIOS_LIMIT,
+            policy_dir.read_bios_limit()?,
+        ));
+    }
+
+    // max_perf_pct is a share of the highest P-state, turbo included.
+    let max_perf_pct_path = Path::new(INTEL_PSTATE_PATH).join("max_perf_pct");
+
+    if let Some(percent) = read_u64(backend, &max_perf_pct_path) {
+        limits.push(Limit {
+            name: "intel_pstate max_perf_pct".to_string(),
+            path: max_perf_pct_path,
+            cap: Frequency::from_hz(rated_max.as_hz() / 100 * percent),
+            detail: Some(format!("{}% of {}", percent, rated_max.to_ghz())),
+        });
+    }
+
+    let boost = read_boost(backend);
+
+    // Without boost, the policy can't go past its base frequency, which only
+    // intel_pstate reports.
+    if let Some((path, false)) = &boost {
+        if let Ok(base) = policy_dir.read_base_frequency() {
+            limits.push(Limit {
+                name: "disabled boost".to_string(),
+                path: path.clone(),
+                cap: base,
+                detail: Some("the base frequency".to_string()),
+            });
+        }
+    }
+
+    limits.sort_by_key(|limit| limit.cap);
+
+    let cpus = policy_dir.read_affected_cpus()?;
+
+    let throttling: Vec<ThermalThrottle> = sample
+        .throttling
+        .iter()
+        .filter(|t| cpus.contains(&t.cpu))
+        .cloned()
+        .collect();
+
+    let power_limits = sample.power_limits.clone();
+
+    let current = policy_dir.read_current_freq()?;
+    let floor = policy_dir.read_scaling_min_freq()?;
+    let lowest_cap = limits[0].cap;
+
+    let near = |frequency: Frequency, target: Frequency| {
+        frequency.as_hz().abs_diff(target.as_hz()) <= target.as_hz() / 100 * AT_LIMIT_PERCENT
+    };
+
+    let capping = |limit: &&Limit| near(limit.cap, lowest_cap);
+
+    let cause = if current >= lowest_cap || near(current, lowest_cap) {
+        // The kernel clamps scaling_max_freq to the other limits, so when
+        // they're equal, the others come first as the actual source.
+        let mut capping: Vec<&Limit> = limits.iter().filter(capping).collect();
+        capping.sort_by_key(|limit| limit.name == attributes::SCALING_MAX_FREQ.description);
+
+        Cause::Limit(capping.iter().map(|limit| limit.name.clone()).collect())
+    } else if throttling
+        .iter()
+        .any(|t| t.core_sampled > 0 || t.package_sampled > 0)
+    {
+        Cause::Thermal
+    } else if let Some(limit) = power_limits.iter().find(|limit| {
+        limit
+            .drawn_uw
+            .is_some_and(|drawn| drawn >= limit.limit_uw / 100 * AT_POWER_LIMIT_PERCENT)
+    }) {
+        Cause::Power {
+            zone: limit.zone.clone(),
+            constraint: limit.constraint.clone(),
+        }
+    } else if current <= floor || near(current, floor) {
+        Cause::Floor
+    } else {
+        Cause::Governor
+    };
+
+    Ok(Explanation {
+        policy: policy_dir.policy_number,
+        current,
+        governor: policy_dir.read_current_governor().ok(),
+        floor,
+        boost: boost.map(|(_, enabled)| enabled),
+        limits,
+        throttling,
+        power_limits,
+        cause,
+    })
+}
+
+fn read_u64<B: CpufreqBackend>(backend: &B, path: &Path) -> Option<u64> {
+    backend.read(path).ok()?.trim().parse::<u64>().ok()
+}
+
+/// Returns whether boost is enabled, along with the switch it was read from.
+fn read_boost<B: CpufreqBackend>(backend: &B) -> Option<(PathBuf, bool)> {
+    let path = boost::boost_path(backend)?;
+    let enabled = boost::read_boost(backend).ok()??;
+
+    Some((PathBuf::from(path), enabled))
+}
+
+/// Returns the core and package thermal throttle counts of a CPU, or None
+/// if it doesn't report them. Only Intel CPUs do, and some lack the package
+/// count, which is then zero.
+fn read_throttle_counts<B: CpufreqBackend>(backend: &B, cpu: u32) -> Option<(u64, u64)> {
+    let dir = Path::new(CPU_PATH)
+        .join(format!("cpu{}", cpu))
+        .join("thermal_throttle");
+
+    Some((
+        read_u64(backend, &dir.join("core_throttle_count"))?,
+        read_u64(backend, &dir.join("package_throttle_count")).unwrap_or(0),
+    ))
+}
+
+/// Returns the directories of the RAPL powercap zones, e.g. intel-rapl:0 for
+/// a package and intel-rapl:0:0 for its cores, sorted by name.
+fn read_power_zones<B: CpufreqBackend>(backend: &B) -> Vec<PathBuf> {
+    let mut zones: Vec<PathBuf> = backend
+        .read_dir(Path::new(POWERCAP_PATH))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy())
+                .is_some_and(|name| name.starts_with("intel-rapl") && name.contains(':'))
+        })
+        .collect();
+
+    zones.sort();
+    zones
+}
+
+/// Returns the enabled power limits of a RAPL zone, one per constraint.
+fn read_power_limits<B: CpufreqBackend>(
+    backend: &B,
+    zone: &Path,
+    drawn_uw: Option<u64>,
+) -> Vec<PowerLimit> {
+    if read_u64(backend, &zone.join("enabled")) == Some(0) {
+        return Vec::new();
+    }
+
+    let name = backend
+        .read(&zone.join("name"))
+        .map(|name| name.trim().to_string())
+        .unwrap_or_else(|_| {
+            zone.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        });
+
+    let mut limits = Vec::new();
+
+    for i in 0.. {
+        let Ok(constraint) = backend.read(&zone.join(format!("constraint_{}_name", i))) else {
+            break;
+        };
+
+        let limit_uw = read_u64(
+            backend,
+            &zone.join(format!("constraint_{}_power_limit_uw", i)),
+        );
+
+        // A limit of zero means the constraint isn't in use.
+        if let Some(limit_uw) = limit_uw.filter(|limit_uw| *limit_uw > 0) {
+            limits.push(PowerLimit {
+                zone: name.clone(),
+                constraint: constraint.trim().to_string(),
+                limit_uw,
+                drawn_uw,
+            });
+        }
+    }
+
+    limits
+}
@@ -1,9 +1,61 @@
+use crate::{
+    error::{self, CpmError},
+    suggest,
+};
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// The unit a frequency is displayed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FrequencyUnit {
+    GHz,
+    MHz,
+    KHz,
+    Hz,
+}
+
+impl FrequencyUnit {
+    /// Returns the number of Hz in one of the unit.
+    pub const fn hz(self) -> u64 {
+        match self {
+            FrequencyUnit::GHz => 1_000_000_000,
+            FrequencyUnit::MHz => 1_000_000,
+            FrequencyUnit::KHz => 1_000,
+            FrequencyUnit::Hz => 1,
+        }
+    }
+
+    pub const fn symbol(self) -> &'static str {
+        match self {
+            FrequencyUnit::GHz => "GHz",
+            FrequencyUnit::MHz => "MHz",
+            FrequencyUnit::KHz => "KHz",
+            FrequencyUnit::Hz => "Hz",
+        }
+    }
+
+    /// Returns the unit named by a symbol in any case, or by its single
+    /// letter shorthand.
+    pub fn from_symbol(symbol: &str) -> Option<Self> {
+        Some(match symbol.to_ascii_lowercase().as_str() {
+            "ghz" | "g" => FrequencyUnit::GHz,
+            "mhz" | "m" => FrequencyUnit::MHz,
+            "khz" | "k" => FrequencyUnit::KHz,
+            "hz" | "h" => FrequencyUnit::Hz,
+            _ => return None,
+        })
+    }
+}
+
+/// An exact frequency, stored as an integer number of Hz. The unit it was
+/// created or parsed in is kept for display only, so 2.4 GHz and 2400 MHz are
+/// equal, but display as they were given.
 #[derive(Debug, Clone, Copy)]
-pub enum Frequency {
-    GHz(f64),
-    MHz(u64),
-    KHz(u64),
-    Hz(u64),
+pub struct Frequency {
+    hz: u64,
+    unit: FrequencyUnit,
 }
 
 // Convenient type aliases.
@@ -11,80 +63,535 @@ pub type Frq = Frequency;
 pub type Freq = Frequency;
 
 impl Frequency {
-    pub fn to_hz(&self) -> Frequency {
-        match self {
-            Frequency::GHz(ghz) => Frequency::Hz((ghz * 1_000_000_000.0) as u64),
-            Frequency::MHz(mhz) => Frequency::Hz(mhz * 1_000_000),
-            Frequency::KHz(khz) => Frequency::Hz(khz * 1_000),
-            hz @ Frequency::Hz(_) => *hz,
+    /// Creates a frequency of the value in the unit, or None if it doesn't
+    /// fit in a u64 of Hz.
+    pub const fn new(value: u64, unit: FrequencyUnit) -> Option<Self> {
+        match value.checked_mul(unit.hz()) {
+            Some(hz) => Some(Self { hz, unit }),
+            None => None,
         }
     }
 
-    pub fn to_khz(&self) -> Frequency {
-        match self {
-            Frequency::GHz(ghz) => Frequency::KHz((ghz * 1_000_000.0) as u64),
-            Frequency::MHz(mhz) => Frequency::KHz(mhz * 1_000),
-            Frequency::Hz(hz) => Frequency::KHz(hz / 1_000),
-            khz @ Frequency::KHz(_) => *khz,
+    pub const fn from_hz(hz: u64) -> Self {
+        Self {
+            hz,
+            unit: FrequencyUnit::Hz,
         }
     }
 
-    pub fn to_mhz(&self) -> Frequency {
-        match self {
-            Frequency::GHz(ghz) => Frequency::MHz((ghz * 1_000.0) as u64),
-            Frequency::KHz(khz) => Frequency::MHz(khz / 1_000),
-            Frequency::Hz(hz) => Frequency::MHz(hz / 1_000_000),
-            mhz @ Frequency::MHz(_) => *mhz,
+    /// Creates a frequency from KHz, the unit sysfs uses. Values too large to
+    /// be held in Hz saturate, use new to detect that instead.
+    pub const fn from_khz(khz: u64) -> Self {
+        Self {
+            hz: khz.saturating_mul(1_000),
+            unit: FrequencyUnit::KHz,
         }
     }
 
-    pub fn to_ghz(&self) -> Frequency {
-        match self {
-            Frequency::MHz(mhz) => Frequency::GHz((*mhz as f64) / 1_000.0),
-            Frequency::KHz(khz) => Frequency::GHz((*khz as f64) / 1_000_000.0),
-            Frequency::Hz(hz) => Frequency::GHz((*hz as f64) / 1_000_000_000.0),
-            ghz @ Frequency::GHz(_) => *ghz,
+    pub const fn as_hz(&self) -> u64 {
+        self.hz
+    }
+
+    /// Returns the frequency in whole KHz, the unit sysfs expects. Any
+    /// remainder below 1 KHz is truncated, use as_whole_khz for values that
+    /// are going to be written.
+    pub const fn as_khz(&self) -> u64 {
+        self.hz / 1_000
+    }
+
+    /// Returns the frequency in KHz, or an error if it isn't a whole number
+    /// of KHz, since sysfs has no way to take the remainder.
+    pub fn as_whole_khz(&self) -> error::Result<u64> {
+        if !self.hz.is_multiple_of(1_000) {
+            return Err(CpmError::Parse(format!(
+                "The frequency '{}' isn't a whole number of KHz, which is the finest step the kernel accepts.",
+                self
+            )));
+        }
+
+        Ok(self.as_khz())
+    }
+
+    pub const fn unit(&self) -> FrequencyUnit {
+        self.unit
+    }
+
+    /// Returns the same frequency, displayed in another unit.
+    pub const fn in_unit(&self, unit: FrequencyUnit) -> Frequency {
+        Frequency { hz: self.hz, unit }
+    }
+
+    pub const fn to_hz(&self) -> Frequency {
+        self.in_unit(FrequencyUnit::Hz)
+    }
+
+    pub const fn to_khz(&self) -> Frequency {
+        self.in_unit(FrequencyUnit::KHz)
+    }
+
+    pub const fn to_mhz(&self) -> Frequency {
+        self.in_unit(FrequencyUnit::MHz)
+    }
+
+    pub const fn to_ghz(&self) -> Frequency {
+        self.in_unit(FrequencyUnit::GHz)
+    }
+
+    /// Adds the frequencies, keeping the unit of self.
+    pub const fn checked_add(self, other: Frequency) -> Option<Frequency> {
+        match self.hz.checked_add(other.hz) {
+            Some(hz) => Some(Frequency {
+                hz,
+                unit: self.unit,
+            }),
+            None => None,
+        }
+    }
+
+    /// Subtracts the frequencies, keeping the unit of self.
+    pub const fn checked_sub(self, other: Frequency) -> Option<Frequency> {
+        match self.hz.checked_sub(other.hz) {
+            Some(hz) => Some(Frequency {
+                hz,
+                unit: self.unit,
+            }),
+            None => None,
+        }
+    }
+
+    pub const fn checked_mul(self, factor: u64) -> Option<Frequency> {
+        match self.hz.checked_mul(factor) {
+            Some(hz) => Some(Frequency {
+                hz,
+                unit: self.unit,
+            }),
+            None => None,
+        }
+    }
+
+    /// Divides the frequency, truncating to whole Hz.
+    pub const fn checked_div(self, divisor: u64) -> Option<Frequency> {
+        match self.hz.checked_div(divisor) {
+            Some(hz) => Some(Frequency {
+                hz,
+                unit: self.unit,
+            }),
+            None => None,
+        }
+    }
+
+    /// Returns the distance between the frequencies, in the unit of self.
+    pub const fn abs_diff(self, other: Frequency) -> Frequency {
+        Frequency {
+            hz: self.hz.abs_diff(other.hz),
+            unit: self.unit,
+        }
+    }
+}
+
+/// How a frequency is rounded to one of the discrete steps a policy supports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Snap {
+    /// The closest step, preferring the lower one on a tie.
+    Nearest,
+    /// The lowest step at or above the frequency.
+    Up,
+    /// The highest step at or below the frequency.
+    Down,
+}
+
+impl FromStr for Snap {
+    type Err = CpmError;
+
+    fn from_str(snap_str: &str) -> error::Result<Snap> {
+        match snap_str.to_lowercase().as_str() {
+            "nearest" => Ok(Snap::Nearest),
+            "up" => Ok(Snap::Up),
+            "down" => Ok(Snap::Down),
+            _ => Err(CpmError::Parse(format!(
+                "Invalid snap mode '{}'.{} Must be one of: nearest, up, down",
+                snap_str,
+                suggest::did_you_mean(snap_str, ["nearest", "up", "down"])
+            ))),
         }
     }
+}
+
+impl Frequency {
+    /// Returns the step the frequency snaps to, displayed in the frequency's
+    /// unit, or None if there are no steps. Up and down fall back to the
+    /// highest or lowest step when no step lies in that direction.
+    pub fn snap(&self, steps: &[Frequency], snap: Snap) -> Option<Frequency> {
+        let below = steps.iter().filter(|step| *step <= self).max();
+        let above = steps.iter().filter(|step| *step >= self).min();
+
+        let step = match (snap, below, above) {
+            (Snap::Up, _, Some(above)) | (Snap::Down, None, Some(above)) => above,
+            (Snap::Down, Some(below), _) | (Snap::Up, Some(below), None) => below,
+            (Snap::Nearest, Some(below), Some(above)) => {
+                match self.abs_diff(*below) <= self.abs_diff(*above) {
+                    true => below,
+                    false => above,
+                }
+            }
+            (Snap::Nearest, Some(step), None) | (Snap::Nearest, None, Some(step)) => step,
+            (_, None, None) => return None,
+        };
+
+        Some(step.in_unit(self.unit))
+    }
+}
+
+impl PartialEq for Frequency {
+    fn eq(&self, other: &Self) -> bool {
+        self.hz == other.hz
+    }
+}
+
+impl Eq for Frequency {}
+
+impl PartialOrd for Frequency {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
 
-    pub fn to_string_u64(&self) -> String {
-        u64::from(*self).to_string()
+impl Ord for Frequency {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.hz.cmp(&other.hz)
     }
+}
 
-    pub fn to_string_f64(&self) -> String {
-        f64::from(*self).to_string()
+impl Hash for Frequency {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hz.hash(state);
     }
 }
 
+/// Displays the exact value in the frequency's unit, with as many decimals as
+/// needed and no more, e.g. 2.4 GHz, 2400 MHz or 2.4005 GHz.
 impl std::fmt::Display for Frequency {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Frequency::GHz(ghz) => write!(f, "{:.2} GHz", ghz),
-            Frequency::MHz(mhz) => write!(f, "{} MHz", mhz),
-            Frequency::KHz(khz) => write!(f, "{} KHz", khz),
-            Frequency::Hz(hz) => write!(f, "{} Hz", hz),
+        let scale = self.unit.hz();
+        let whole = self.hz / scale;
+        let fraction = self.hz % scale;
+
+        if fraction == 0 {
+            return write!(f, "{} {}", whole, self.unit.symbol());
         }
+
+        let digits = format!("{:0width$}", fraction, width = scale.ilog10() as usize);
+        write!(
+            f,
+            "{}.{} {}",
+            whole,
+            digits.trim_end_matches('0'),
+            self.unit.symbol()
+        )
     }
 }
 
-impl From<Frequency> for f64 {
-    fn from(value: Frequency) -> Self {
-        match value {
-            Frequency::GHz(ghz) => ghz,
-            Frequency::MHz(mhz) => mhz as f64,
-            Frequency::KHz(khz) => khz as f64,
-            Frequency::Hz(hz) => hz as f64,
+/// An error from parsing a frequency or a frequency range, pointing at the
+/// character that caused it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFrequencyError {
+    pub input: String,
+    /// The index of the offending character, counted in chars. Equal to the
+    /// length of the input when the input ended too early.
+    pub position: usize,
+    pub reason: String,
+}
+
+impl std::fmt::Display for ParseFrequencyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Invalid frequency '{}', {}.\n    {}\n    {}^",
+            self.input,
+            self.reason,
+            self.input,
+            " ".repeat(self.position)
+        )
+    }
+}
+
+impl std::error::Error for ParseFrequencyError {}
+
+impl From<ParseFrequencyError> for CpmError {
+    fn from(error: ParseFrequencyError) -> Self {
+        CpmError::Parse(error.to_string())
+    }
+}
+
+/// Parses what Display writes, as well as the shorthand accepted on the
+/// command line. See parse_freq.
+impl FromStr for Frequency {
+    type Err = ParseFrequencyError;
+
+    fn from_str(freq_str: &str) -> Result<Frequency, ParseFrequencyError> {
+        let chars: Vec<char> = freq_str.chars().collect();
+        parse_frequency_at(freq_str, &chars, 0, chars.len())
+    }
+}
+
+/// Parses the frequency in chars[start..end]. Errors point into the whole
+/// input, so a range can report positions in its max side correctly.
+fn parse_frequency_at(
+    input: &str,
+    chars: &[char],
+    start: usize,
+    end: usize,
+) -> Result<Frequency, ParseFrequencyError> {
+    let error = |position: usize, reason: String| ParseFrequencyError {
+        input: input.to_string(),
+        position,
+        reason,
+    };
+
+    let is_digit_at = |i: usize| i < end && chars[i].is_ascii_digit();
+    let skip_whitespace = |mut i: usize| {
+        while i < end && chars[i].is_whitespace() {
+            i += 1;
+        }
+        i
+    };
+
+    let mut i = skip_whitespace(start);
+    let number_start = i;
+
+    let mut digits = String::new();
+    let mut fraction_len: i64 = 0;
+    let mut seen_point = false;
+
+    while i < end {
+        match chars[i] {
+            c if c.is_ascii_digit() => {
+                digits.push(c);
+                fraction_len += i64::from(seen_point);
+            }
+
+            // Separators are only allowed between two digits, e.g. 3,500 or 3_500.
+            c @ (',' | '_') => {
+                if i == start || !chars[i - 1].is_ascii_digit() || !is_digit_at(i + 1) {
+                    return Err(error(i, format!("unexpected separator '{}'", c)));
+                }
+            }
+
+            '.' if seen_point => return Err(error(i, "unexpected second '.'".to_string())),
+            '.' => seen_point = true,
+            _ => break,
+        }
+
+        i += 1;
+    }
+
+    if digits.is_empty() {
+        return Err(error(i, "expected a number".to_string()));
+    }
+
+    let mut exponent: i64 = 0;
+
+    if i < end && matches!(chars[i], 'e' | 'E') {
+        let exponent_start = i;
+        i += 1;
+
+        let negative = i < end && chars[i] == '-';
+
+        if i < end && matches!(chars[i], '+' | '-') {
+            i += 1;
+        }
+
+        if !is_digit_at(i) {
+            return Err(error(i, "expected the digits of the exponent".to_string()));
+        }
+
+        let digits_start = i;
+
+        while is_digit_at(i) {
+            i += 1;
         }
+
+        exponent = chars[digits_start..i]
+            .iter()
+            .collect::<String>()
+            .parse::<i64>()
+            .ok()
+            .filter(|exponent| *exponent <= 64)
+            .ok_or_else(|| error(exponent_start, "the exponent is out of range".to_string()))?;
+
+        if negative {
+            exponent = -exponent;
+        }
+    }
+
+    i = skip_whitespace(i);
+
+    let unit_start = i;
+
+    while i < end && chars[i].is_ascii_alphabetic() {
+        i += 1;
     }
+
+    let symbol: String = chars[unit_start..i].iter().collect();
+
+    let unit = match symbol.as_str() {
+        // Without a unit, values containing a period are GHz, and others are KHz.
+        "" if seen_point => FrequencyUnit::GHz,
+        "" => FrequencyUnit::KHz,
+        symbol => FrequencyUnit::from_symbol(symbol).ok_or_else(|| {
+            error(
+                unit_start,
+                format!(
+                    "unknown unit '{}', expected GHz, MHz, KHz, Hz, or g, m, k, h",
+                    symbol
+                ),
+            )
+        })?,
+    };
+
+    i = skip_whitespace(i);
+
+    if i < end {
+        return Err(error(i, format!("unexpected '{}'", chars[i])));
+    }
+
+    // The value is digits * 10^(exponent - fraction_len) in the unit, which
+    // is computed exactly in integers, and has to come out as whole Hz.
+    let too_large = || error(number_start, "the value is too large".to_string());
+
+    let significant = digits.trim_start_matches('0');
+
+    let mantissa = match significant {
+        "" => 0,
+        significant if significant.len() > 38 => return Err(too_large()),
+        significant => significant.parse::<u128>().map_err(|_| too_large())?,
+    };
+
+    let scale = exponent - fraction_len + i64::from(unit.hz().ilog10());
+
+    let hz = if mantissa == 0 {
+        0
+    } else if scale >= 0 {
+        u32::try_from(scale)
+            .ok()
+            .and_then(|scale| 10u128.checked_pow(scale))
+            .and_then(|factor| mantissa.checked_mul(factor))
+            .ok_or_else(too_large)?
+    } else {
+        let divisor = u32::try_from(-scale)
+            .ok()
+            .and_then(|scale| 10u128.checked_pow(scale));
+
+        match divisor {
+            Some(divisor) if mantissa % divisor == 0 => mantissa / divisor,
+            _ => {
+                return Err(error(
+                    number_start,
+                    "the value is more precise than 1 Hz".to_string(),
+                ))
+            }
+        }
+    };
+
+    let hz = u64::try_from(hz).map_err(|_| too_large())?;
+
+    Ok(Frequency { hz, unit })
 }
 
-impl From<Frequency> for u64 {
-    fn from(value: Frequency) -> Self {
-        match value {
-            Frequency::GHz(ghz) => ghz as u64,
-            Frequency::MHz(mhz) => mhz,
-            Frequency::KHz(khz) => khz,
-            Frequency::Hz(hz) => hz,
+/// A min and max frequency, either of which may be left out, as given on the
+/// command line in the format <min>:<max>, :<max> or <min>:.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrequencyRange {
+    pub min: Option<Frequency>,
+    pub max: Option<Frequency>,
+}
+
+impl std::fmt::Display for FrequencyRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(min) = self.min {
+            write!(f, "{}", min)?;
+        }
+
+        write!(f, ":")?;
+
+        if let Some(max) = self.max {
+            write!(f, "{}", max)?;
         }
+
+        Ok(())
     }
 }
+
+impl FromStr for FrequencyRange {
+    type Err = ParseFrequencyError;
+
+    fn from_str(range_str: &str) -> Result<FrequencyRange, ParseFrequencyError> {
+        let chars: Vec<char> = range_str.chars().collect();
+
+        let error = |position: usize, reason: &str| ParseFrequencyError {
+            input: range_str.to_string(),
+            position,
+            reason: reason.to_string(),
+        };
+
+        let mut colons = chars
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c == ':')
+            .map(|(i, _)| i);
+
+        let colon = colons.next().ok_or_else(|| {
+            error(
+                chars.len(),
+                "expected a ':' between min and max, e.g. 2.5:3.5, :3.5 or 2.5:",
+            )
+        })?;
+
+        if let Some(second) = colons.next() {
+            return Err(error(second, "unexpected second ':'"));
+        }
+
+        let is_blank = |from: usize, to: usize| chars[from..to].iter().all(|c| c.is_whitespace());
+
+        let min = match is_blank(0, colon) {
+            true => None,
+            false => Some(parse_frequency_at(range_str, &chars, 0, colon)?),
+        };
+
+        let max = match is_blank(colon + 1, chars.len()) {
+            true => None,
+            false => Some(parse_frequency_at(
+                range_str,
+                &chars,
+                colon + 1,
+                chars.len(),
+            )?),
+        };
+
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                let max_start = (colon + 1..chars.len())
+                    .find(|i| !chars[*i].is_whitespace())
+                    .unwrap_or(colon + 1);
+
+                return Err(error(max_start, "the max is lower than the min"));
+            }
+        }
+
+        if min.is_none() && max.is_none() {
+            return Err(error(
+                colon,
+                "expected a frequency on at least one side of the ':'",
+            ));
+        }
+
+        Ok(FrequencyRange { min, max })
+    }
+}
+
+/// Parses a single frequency value, e.g. 3.5, 3.5 GHz, 3,500m, 3_500_000 or
+/// 3.5e9 Hz. Units can be given in any case, and when no unit is given, values
+/// containing a period are GHz, and others are KHz.
+pub fn parse_freq(freq_str: &str) -> error::Result<Frequency> {
+    Ok(freq_str.parse()?)
+}
@@ -1 +1,20 @@
 pub const CPU_FREQ_PATH: &str = "/sys/devices/system/cpu/cpufreq/";
+pub const CPU_PATH: &str = "/sys/devices/system/cpu/";
+
+pub const BOOST_PATH: &str = "/sys/devices/system/cpu/cpufreq/boost";
+pub const NO_TURBO_PATH: &str = "/sys/devices/system/cpu/intel_pstate/no_turbo";
+
+pub const INTEL_PSTATE_PATH: &str = "/sys/devices/system/cpu/intel_pstate/";
+pub const POWERCAP_PATH: &str = "/sys/class/powercap/";
+
+pub const LOCK_STATE_PATH: &str = "/run/cpm/lock.state";
+
+pub const SMT_CONTROL_PATH: &str = "/sys/devices/system/cpu/smt/control";
+pub const SMT_ACTIVE_PATH: &str = "/sys/devices/system/cpu/smt/active";
+
+pub const UNCORE_FREQ_PATH: &str = "/sys/devices/system/cpu/intel_uncore_frequency/";
+
+pub const PLATFORM_PROFILE_PATH: &str = "/sys/firmware/acpi/platform_profile";
+pub const PLATFORM_PROFILE_CHOICES_PATH: &str = "/sys/firmware/acpi/platform_profile_choices";
+
+pub const PROFILES_PATH: &str = "/etc/cpm/profiles.conf";
@@ -0,0 +1,321 @@
+//! The help text, split into a section per command, so that
+//! `cpm <command> --help` prints only the part that's relevant.
+
+pub const USAGE: &str = "\
+Usage: cpm <command> [arguments]
+
+Commands: set, get, info, explain, lock, unlock, idle, cpu, smt, uncore,
+          profile
+
+Run 'cpm <command> --help' for the usage of a single command.
+";
+
+pub const SET: &str = "\
+Setting Values:
+cpm set <policies> <attribute> <value> [<attribute> <value>...]
+        [--snap nearest|up|down] [--keep-going]
+
+Every attribute is validated against every policy before any is written,
+so a single invalid value leaves the policies untouched.
+
+Options:
+    --snap nearest|up|down - Snaps frequencies to the policy's steps.
+    --keep-going           - Writes whatever validates even when some
+                             attributes don't, then prints a table of each
+                             result, and exits with the code of the first
+                             failure, if any.
+
+Example:
+    cpm set all freq 3.0:4.5
+        Sets the min and max frequency for all governors to 3.0 and 4.5
+
+    cpm set all freq 1.2:3.0 gov powersave perf balance_power
+        Sets the frequency range, governor and performance profile at once
+
+<policies> format:
+    0     - A single policy number.
+    0:4   - A range of policy numbers.
+    0,2,5 - Specific policy number.
+    all|* - Affects every policy.
+
+<attribute> format:
+    freq - CPU Frequency
+    gov  - CPU Governor
+    perf - CPU Performance Profile
+    epb  - CPU Energy Performance Bias (Intel)
+    raw  - Any file in the policy directory
+
+<value> format:
+    freq: <min>:<max>
+        If no suffix is provided, and a period is present, defualts to GHz
+        2.5:3.5 - Sets scaling min/max to 2.5 and 3.5 GHz
+
+        If no suffix is provided, and no periods are present, defaults to KHz
+        2500000:3500000 - Sets scaling min/max to 2.5/3.5 GHz
+
+        To set only min, or only max, provide no value on the left or right.
+        The inclusion of a : is mandatory however.
+
+        :3.5 - Sets scaling max to 3.5 GHz
+        2.5: - Sets scaling min to 2.5 Ghz
+
+        Available units: GHz, MHz, KHz, Hz, or g, m, k, h, in any case
+
+        :3.5g            - Sets scaling max to 3.5 GHz
+        :3,500m          - Sets scaling max to 3,500 MHz        (3.5GHz)
+        :3,500,000k      - Sets scaling max to 3,500,000 KHz    (3.5GHz)
+        :3,500,000,000h  - Sets scaling max to 3,500,000,000 Hz (3.5GHz)
+
+        Comas and underscores are ignored, so feel free to use them for
+        readability. Spaces and scientific notation work too.
+
+        :3.5 GHz         - Sets scaling max to 3.5 GHz
+        :3_500_000       - Sets scaling max to 3,500,000 KHz    (3.5GHz)
+        :3.5e9 Hz        - Sets scaling max to 3,500,000,000 Hz (3.5GHz)
+
+        Units can also be mixed
+
+        2,500m:3,500,000k - Sets scaling min/max to 2.5/3.5Ghz
+
+        Some drivers, e.g. acpi-cpufreq, only run at discrete steps, and
+        the driver may adjust the value to a supported step. cpm reports the
+        step a value resolves to, and --snap writes the nearest step, the one
+        above, or the one below instead. List the steps with
+            cpm get 0 freq avail
+
+    gov: <governor>
+        Must be a valid CPU governor. You can check available governors using
+            cpm get 0 gov avail
+
+        Then set it like this
+            cpm set all gov powersave
+
+    perf: <profile>
+        Must be a valid performance profile. Get available profiles with
+            cpm get 0 perf avail
+
+        Then set it like this
+            cpm set all perf balance_performance
+
+        With HWP, a raw EPP value from 0 (performance) to 255 (power) works too
+            cpm set all perf 96
+
+    epb: <bias>
+        A number from 0 (performance) to 15 (power), or one of the names
+            performance, balance-performance, normal, balance-power, power
+
+        Applied to every CPU of each policy, e.g.
+            cpm set all epb balance-power
+
+    raw: <file> <value>
+        Writes any writable file in the policy directory, including ones cpm
+        doesn't otherwise know about. List them with
+            cpm get 0 raw list
+
+        Then set one like this
+            cpm set all raw energy_performance_preference 96
+";
+
+pub const GET: &str = "\
+Getting Values:
+    cpm get <policies> [attribute] [value]
+
+    <policies> format:
+        0     - A single policy number.
+        0:4   - A range of policy numbers.
+        0,2,5 - Specific policy number.
+
+        (all | *)   - Every policy.
+
+    <attribute> format:
+        freq - CPU Frequency
+        gov  - CPU Governor
+        perf - CPU Performance Profile
+        epb  - CPU Energy Performance Bias (Intel)
+        raw  - Any file in the policy directory
+        all  - The frequencies, governors, EPP and driver at once, which is
+               also what's printed when no attribute is given
+
+    <value> format:
+        freq: min, max, (curr | current), (avail | available)
+        gov:  (curr | current), (avail | available)
+        perf: (curr | current), (avail | available)
+        epb:  (curr | current)
+        raw:  list, <file>
+
+    Example:
+        cpm get all
+        cpm get 0:3 gov avail
+
+    Any other policy file can be read by its name or alias, e.g.
+        cpm get 0 driver
+        cpm get 0 freq base
+        cpm get all scaling_available_governors
+
+    Aliases: cpus, cpus related, driver, latency, freq base, freq rated_min,
+             freq rated_max, freq bios_limit, freq curr, freq avail, freq min,
+             freq max, gov, gov avail, perf, perf avail, setspeed
+";
+
+pub const INFO: &str = "\
+Policy Summary:
+    cpm info [policies] [--json]
+
+    Prints every policy file of each policy, defaulting to all. Files the
+    policy doesn't have are shown as n/a, or null with --json.
+";
+
+pub const EXPLAIN: &str = "\
+Explaining Frequency:
+    cpm explain <policies> [--sample <ms>]
+
+    Gathers every limit on the frequency of each policy, scaling min and max,
+    the rated limits, bios_limit, intel_pstate's max_perf_pct and boost, then
+    samples the thermal throttle counters and RAPL power draw for 250 ms, or
+    the given number of milliseconds, and states which is capping it.
+
+    Example:
+        cpm explain 0
+        cpm explain all --sample 1000
+";
+
+pub const LOCK: &str = "\
+Locking Frequency:
+    cpm lock <freq> [policies] [--no-idle]
+
+    Pins scaling min and max to <freq>, switches to the performance governor
+    and performance profile, and disables boost, for reproducible benchmarks.
+    Policies default to all. --no-idle also disables idle states deeper than C1.
+
+    Example:
+        cpm lock 2.4
+        cpm lock 2,400m 0:3 --no-idle
+
+    cpm unlock
+
+    Restores everything cpm lock changed to exactly how it was before.
+";
+
+pub const IDLE: &str = "\
+Idle States:
+    cpm idle show <policies|cpus>
+    cpm idle disable <policies|cpus> <state>
+    cpm idle enable <policies|cpus> <state>
+
+    <cpus> format:
+        cpu3, cpu0-3, cpu0,2,5 - CPU numbers rather than policy numbers.
+
+    <state> format:
+        2 | state2 | C1E - The state index, directory, or name.
+        all              - Every idle state.
+
+    Example:
+        cpm idle show all
+        cpm idle disable cpu0-3 C6
+";
+
+pub const CPU: &str = "\
+CPU Hotplug:
+    cpm cpu online <cpulist>
+    cpm cpu offline <cpulist> [--force]
+
+    <cpulist> format:
+        2, 2-5, 0,2,5, all - CPU numbers, in the kernel's cpulist format.
+
+    CPUs that aren't hotpluggable, usually CPU0, are never taken offline, and
+    neither is the last online CPU of a package unless --force is given.
+";
+
+pub const SMT: &str = "\
+SMT Control:
+    cpm smt (on | off | forceoff | status)
+
+    Enables or disables sibling threads, and reports which policies and
+    affected CPUs changed as a result. forceoff lasts until reboot.
+";
+
+pub const UNCORE: &str = "\
+Uncore Frequency (Intel):
+    cpm uncore get <packages> (min | max | curr | all)
+    cpm uncore set <packages> <min>:<max>
+
+    <packages> is a package number, range or list, or all. The value takes
+    the same format as freq above, and must fall within the limits the
+    firmware set at boot.
+
+    Example:
+        cpm uncore set all 1.2:2.4
+";
+
+pub const PLATFORM: &str = "\
+Platform Profile (ACPI):
+    cpm get platform (curr | current | avail | available)
+    cpm set platform <profile>
+
+    Example:
+        cpm get platform avail
+        cpm set platform quiet
+";
+
+pub const PROFILE: &str = "\
+Profiles:
+    cpm profile <name>
+    cpm profile list
+
+    Applies a named profile from /etc/cpm/profiles.conf, where each [section]
+    is a profile, and each line a setting taking the same values as cpm set.
+
+        [quiet]
+        platform = quiet
+        policies = all
+        gov = powersave
+        perf = power
+        freq = :2.0
+
+    Valid settings: platform, policies, freq, gov, perf, epb
+";
+
+pub const EXIT_CODES: &str = "\
+Exit Codes:
+    0  Success
+    1  Other error, e.g. invalid arguments
+    2  A file or directory doesn't exist
+    3  Permission denied
+    4  A value is out of range
+    5  The value or feature isn't supported
+    6  A value couldn't be parsed
+    7  The policy is inactive, because all of its CPUs are offline
+    8  Any other I/O error
+    9  The kernel clamped or ignored a value, which read back differently
+";
+
+/// Every command, along with the sections of the help text documenting it.
+pub const COMMANDS: [(&str, &[&str]); 11] = [
+    ("set", &[SET, PLATFORM]),
+    ("get", &[GET, PLATFORM]),
+    ("info", &[INFO]),
+    ("explain", &[EXPLAIN]),
+    ("lock", &[LOCK]),
+    ("unlock", &[LOCK]),
+    ("idle", &[IDLE]),
+    ("cpu", &[CPU]),
+    ("smt", &[SMT]),
+    ("uncore", &[UNCORE]),
+    ("profile", &[PROFILE]),
+];
+
+/// Returns the help text of a single command, if it's one.
+pub fn command(name: &str) -> Option<String> {
+    COMMANDS
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map(|(_, sections)| sections.join("\n"))
+}
+
+/// Returns the full help text, with every section.
+pub fn full() -> String {
+    [
+        USAGE, SET, GET, INFO, EXPLAIN, LOCK, IDLE, CPU, SMT, UNCORE, PLATFORM, PROFILE, EXIT_CODES,
+    ]
+    .join("\n")
+}
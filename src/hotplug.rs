@@ -0,0 +1,148 @@
+use crate::{
+    backend::CpufreqBackend,
+    cpulist,
+    error::{self, CpmError},
+    CPU_PATH,
+};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Returns the path of the online file of the CPU. The kernel only creates
+/// this file for CPUs that can be hotplugged.
+pub fn online_path(cpu: u32) -> PathBuf {
+    Path::new(CPU_PATH)
+        .join(format!("cpu{}", cpu))
+        .join("online")
+}
+
+/// Returns whether the CPU can be taken offline. On most x86 systems CPU0
+/// can't be, and has no online file.
+pub fn is_hotpluggable<B: CpufreqBackend>(backend: &B, cpu: u32) -> bool {
+    backend.is_file(&online_path(cpu))
+}
+
+/// Returns the CPUs that are currently online, from the system wide cpulist.
+pub fn read_online_cpus<B: CpufreqBackend>(backend: &B) -> error::Result<Vec<u32>> {
+    read_cpu_list(backend, "online")
+}
+
+/// Returns the CPUs that are present in the system, whether online or not.
+pub fn read_present_cpus<B: CpufreqBackend>(backend: &B) -> error::Result<Vec<u32>> {
+    read_cpu_list(backend, "present")
+}
+
+/// Returns the physical package (socket) the CPU belongs to. The topology
+/// directory only exists while the CPU is online.
+pub fn read_package_id<B: CpufreqBackend>(backend: &B, cpu: u32) -> error::Result<u32> {
+    let path = Path::new(CPU_PATH)
+        .join(format!("cpu{}", cpu))
+        .join("topology")
+        .join("physical_package_id");
+
+    let content = backend
+        .read(&path)
+        .map_err(|e| CpmError::from_io(&path, e))?;
+
+    content.trim().parse::<u32>().map_err(|_| {
+        CpmError::Parse(format!(
+            "Invalid package id '{}' in '{}'.",
+            content.trim(),
+            path.display()
+        ))
+    })
+}
+
+/// Brings the CPUs online. CPUs that are already online are left alone.
+pub fn set_online<B: CpufreqBackend>(backend: &B, cpus: &[u32]) -> error::Result<()> {
+    let online = read_online_cpus(backend)?;
+
+    for cpu in cpus.iter().filter(|cpu| !online.contains(cpu)) {
+        write_online(backend, *cpu, true)?;
+    }
+
+    Ok(())
+}
+
+/// Takes the CPUs offline. CPUs that aren't hotpluggable are refused, and so
+/// is taking the last online CPU of a package offline, unless forced.
+pub fn set_offline<B: CpufreqBackend>(backend: &B, cpus: &[u32], force: bool) -> error::Result<()> {
+    let online = read_online_cpus(backend)?;
+    let mut targets: Vec<u32> = cpus
+        .iter()
+        .copied()
+        .filter(|cpu| online.contains(cpu))
+        .collect();
+
+    // A CPU listed twice must only be counted once against its package.
+    targets.sort_unstable();
+    targets.dedup();
+
+    if let Some(cpu) = targets.iter().find(|cpu| !is_hotpluggable(backend, **cpu)) {
+        return Err(CpmError::Unsupported(format!(
+            "CPU {} isn't hotpluggable, and can't be taken offline.",
+            cpu
+        )));
+    }
+
+    if online.iter().all(|cpu| targets.contains(cpu)) {
+        return Err(CpmError::Unsupported(
+            "Refusing to take every online CPU offline.".to_string(),
+        ));
+    }
+
+    if !force {
+        let mut remaining: HashMap<u32, usize> = HashMap::new();
+
+        for cpu in &online {
+            *remaining
+                .entry(read_package_id(backend, *cpu)?)
+                .or_default() += 1;
+        }
+
+        for cpu in &targets {
+            let package = read_package_id(backend, *cpu)?;
+            let count = remaining.entry(package).or_default();
+            *count -= 1;
+
+            if *count == 0 {
+                return Err(CpmError::Unsupported(format!(
+                    "Taking CPU {} offline would leave package {} without an online CPU. Use --force to do it anyway.",
+                    cpu,
+                    package
+                )));
+            }
+        }
+    }
+
+    for cpu in targets {
+        write_online(backend, cpu, false)?;
+    }
+
+    Ok(())
+}
+
+fn write_online<B: CpufreqBackend>(backend: &B, cpu: u32, online: bool) -> error::Result<()> {
+    let path = online_path(cpu);
+
+    if !backend.is_file(&path) {
+        return Err(CpmError::Unsupported(format!(
+            "CPU {} doesn't exist or isn't hotpluggable.",
+            cpu
+        )));
+    }
+
+    backend
+        .write(&path, if online { "1" } else { "0" })
+        .map_err(|e| CpmError::from_io(&path, e))
+}
+
+fn read_cpu_list<B: CpufreqBackend>(backend: &B, file_name: &str) -> error::Result<Vec<u32>> {
+    let path = Path::new(CPU_PATH).join(file_name);
+
+    let content = backend
+        .read(&path)
+        .map_err(|e| CpmError::from_io(&path, e))?;
+
+    cpulist::parse(&content)
+}
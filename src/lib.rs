@@ -0,0 +1,39 @@
+//! Reads and changes CPU frequency scaling settings through the cpufreq sysfs
+//! interface. PolicyDir is the entry point for a single policy, and the other
+//! modules cover the settings that live outside of the policy directories.
+//!
+//! The command line parser is only built with the `cli` feature, which is on
+//! by default. Library consumers can disable it with `default-features = false`.
+
+#[cfg(feature = "cli")]
+pub mod argparse;
+pub mod attributes;
+pub mod backend;
+pub mod boost;
+pub mod cpuidle;
+pub mod cpulist;
+pub mod epb;
+pub mod error;
+pub mod explain;
+pub mod frequency;
+#[cfg(feature = "cli")]
+pub mod help;
+pub mod hotplug;
+pub mod lock;
+pub mod macros;
+pub mod platform;
+pub mod policies;
+pub mod profiles;
+pub mod simulator;
+pub mod smt;
+pub mod suggest;
+pub mod uncore;
+
+pub mod globals;
+pub use globals::*;
+
+pub use backend::{CpufreqBackend, InMemory, Recording, Sysfs};
+pub use error::{CpmError, Result};
+pub use frequency::Frequency;
+pub use policies::PolicyDir;
+pub use simulator::Simulator;
@@ -0,0 +1,375 @@
+use crate::{
+    attributes::{self, Attribute, ATTRIBUTES},
+    backend::CpufreqBackend,
+    boost, cpuidle,
+    error::{self, CpmError},
+    frequency::Frequency,
+    policies::PolicyDir,
+    LOCK_STATE_PATH,
+};
+
+use std::path::Path;
+
+/// The number of cpuidle states, counting from state0, that are left enabled
+/// when deep idle states are disabled. On both intel_idle and acpi_idle these
+/// are the polling state and C1.
+const SHALLOW_IDLE_STATES: u32 = 2;
+
+/// The governor and energy performance preference a locked policy runs with.
+const LOCK_GOVERNOR: &str = "performance";
+const LOCK_PERF_PROFILE: &str = "performance";
+
+/// The tunables of a single policy as they were before it was locked, i.e.
+/// the raw contents of every attribute the registry marks for snapshots.
+#[derive(Debug, Clone)]
+pub struct PolicyState {
+    pub policy_number: u32,
+    pub values: Vec<(&'static Attribute, String)>,
+}
+
+impl PolicyState {
+    /// Returns the captured raw value of an attribute.
+    pub fn get(&self, attribute: &Attribute) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(a, _)| a.file_name == attribute.file_name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// The value of a cpuidle state's disable file before it was locked.
+#[derive(Debug, Clone)]
+pub struct IdleStateSnapshot {
+    pub cpu: u32,
+    pub state: String,
+    pub disable: String,
+}
+
+/// Everything `cpm lock` changes, captured beforehand so that `cpm unlock`
+/// can write it back exactly as it was.
+#[derive(Debug, Clone, Default)]
+pub struct LockState {
+    pub boost: Option<bool>,
+    pub policies: Vec<PolicyState>,
+    pub idle_states: Vec<IdleStateSnapshot>,
+}
+
+impl LockState {
+    /// Captures the current state of the given policies, and of the deep idle
+    /// states of their CPUs if those are going to be disabled.
+    pub fn capture<B: CpufreqBackend>(
+        backend: &B,
+        policy_dirs: &[PolicyDir<B>],
+        with_idle: bool,
+    ) -> error::Result<Self> {
+        let mut state = LockState {
+            boost: boost::read_boost(backend)?,
+            ..Default::default()
+        };
+
+        for policy_dir in policy_dirs {
+            let mut values: Vec<(&'static Attribute, String)> = Vec::new();
+
+            for attribute in ATTRIBUTES.iter().filter(|a| a.snapshot) {
+                if policy_dir.has_file(attribute) {
+                    values.push((attribute, policy_dir.read(attribute)?.trim().to_string()));
+                }
+            }
+
+            state.policies.push(PolicyState {
+                policy_number: policy_dir.policy_number,
+                values,
+            });
+
+            if !with_idle {
+                continue;
+            }
+
+            for cpu in policy_dir.read_affected_cpus()? {
+                for idle_state in cpuidle::read_idle_states(backend, cpu)? {
+                    if idle_state.index < SHALLOW_IDLE_STATES {
+                        continue;
+                    }
+
+                    state.idle_states.push(IdleStateSnapshot {
+                        cpu,
+                        state: idle_state.dir_name(),
+                        disable: (idle_state.disabled as u8).to_string(),
+                    });
+                }
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Serializes the state into a line based format, one entry per line.
+    ///
+    /// boost <0|1>
+    /// policy <number> <file>=<value> <file>=<value> ...
+    /// idle <cpu> <state> <disable>
+    pub fn serialize(&self) -> String {
+        let mut lines: Vec<String> = Vec::new();
+
+        if let Some(boost) = self.boost {
+            lines.push(format!("boost {}", boost as u8));
+        }
+
+        for policy in &self.policies {
+            let values: Vec<String> = policy
+                .values
+                .iter()
+                .map(|(attribute, value)| format!("{}={}", attribute.file_name, value))
+                .collect();
+
+            lines.push(format!(
+                "policy {} {}",
+                policy.policy_number,
+                values.join(" ")
+            ));
+        }
+
+        for idle in &self.idle_states {
+            lines.push(format!("idle {} {} {}", idle.cpu, idle.state, idle.disable));
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    /// Parses the format produced by serialize.
+    pub fn parse(content: &str) -> error::Result<Self> {
+        let mut state = LockState::default();
+        let malformed =
+            |line: &str| CpmError::Parse(format!("Malformed line in lock state: '{}'", line));
+
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            match fields.as_slice() {
+                ["boost", value] => state.boost = Some(*value == "1"),
+
+                ["policy", number, values @ ..] => {
+                    let values = values
+                        .iter()
+                        .map(|field| {
+                            let (file_name, value) =
+                                field.split_once('=').ok_or(CpmError::Parse(format!(
+                                    "Malformed value '{}' in lock state.",
+                                    field
+                                )))?;
+
+                            let attribute = attributes::find(file_name).ok_or(CpmError::Parse(
+                                format!("Unknown attribute '{}' in lock state.", file_name),
+                            ))?;
+
+                            Ok((attribute, value.to_string()))
+                        })
+                        .collect::<error::Result<_>>()?;
+
+                    state.policies.push(PolicyState {
+                        policy_number: number.parse::<u32>().map_err(|_| malformed(line))?,
+                        values,
+                    })
+                }
+
+                ["idle", cpu, state_name, disable] => state.idle_states.push(IdleStateSnapshot {
+                    cpu: cpu.parse::<u32>().map_err(|_| malformed(line))?,
+                    state: state_name.to_string(),
+                    disable: disable.to_string(),
+                }),
+
+                _ => return Err(malformed(line)),
+            }
+        }
+
+        Ok(state)
+    }
+
+    /// Loads the state saved by a previous lock, if there is one.
+    pub fn load<B: CpufreqBackend>(backend: &B) -> error::Result<Option<Self>> {
+        let path = Path::new(LOCK_STATE_PATH);
+
+        if !backend.is_file(path) {
+            return Ok(None);
+        }
+
+        let content = backend.read(path).map_err(|e| CpmError::from_io(path, e))?;
+
+        Self::parse(&content).map(Some)
+    }
+
+    pub fn save<B: CpufreqBackend>(&self, backend: &B) -> error::Result<()> {
+        let path = Path::new(LOCK_STATE_PATH);
+
+        backend
+            .create(path, &self.serialize())
+            .map_err(|e| CpmError::from_io(path, e))
+    }
+
+    /// Writes every captured value back. Boost comes first, since turning it
+    /// back on raises cpuinfo_max_freq, which a turbo scaling max needs. The
+    /// rest follow in the registry's order, so that the governor is restored
+    /// before the performance profile, and scaling min and max last.
+    pub fn restore<B: CpufreqBackend>(
+        &self,
+        backend: &B,
+        policy_dirs: &[PolicyDir<B>],
+    ) -> error::Result<()> {
+        if let Some(enabled) = self.boost {
+            boost::set_boost(backend, enabled)?;
+        }
+
+        for policy in &self.policies {
+            let policy_dir = policy_dirs
+                .iter()
+                .find(|pd| pd.policy_number == policy.policy_number)
+                .ok_or(CpmError::Unsupported(format!(
+                    "Policy {} from the lock state no longer exists.",
+                    policy.policy_number
+                )))?;
+
+            for attribute in ATTRIBUTES {
+                let is_range = attribute.file_name == attributes::SCALING_MIN_FREQ.file_name
+                    || attribute.file_name == attributes::SCALING_MAX_FREQ.file_name;
+
+                if let (false, Some(value)) = (is_range, policy.get(attribute)) {
+                    policy_dir.write(attribute, value)?;
+                }
+            }
+
+            if let (Some(min), Some(max)) = (
+                policy.get(&attributes::SCALING_MIN_FREQ),
+                policy.get(&attributes::SCALING_MAX_FREQ),
+            ) {
+                let parse = |value: &str| {
+                    value.parse::<u64>().map(Frequency::from_khz).map_err(|_| {
+                        CpmError::Parse(format!("Invalid frequency '{}' in lock state.", value))
+                    })
+                };
+
+                policy_dir.set_scaling_range(Some(&parse(min)?), Some(&parse(max)?))?;
+            }
+        }
+
+        for idle in &self.idle_states {
+            cpuidle::write_disable(backend, idle.cpu, &idle.state, &idle.disable)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Pins the given policies to a single frequency for reproducible benchmarks.
+/// The prior state is saved first, and restored again if any step fails.
+pub fn lock<B: CpufreqBackend>(
+    backend: &B,
+    policy_dirs: &[PolicyDir<B>],
+    frequency: &Frequency,
+    disable_idle: bool,
+) -> error::Result<()> {
+    if LockState::load(backend)?.is_some() {
+        return Err(CpmError::Unsupported(
+            "The CPU is already locked. Run 'cpm unlock' first.".to_string(),
+        ));
+    }
+
+    for policy_dir in policy_dirs {
+        if !policy_dir
+            .read_available_governors()?
+            .iter()
+            .any(|g| g == LOCK_GOVERNOR)
+        {
+            return Err(CpmError::Unsupported(format!(
+                "The '{}' governor isn't available for policy {}.",
+                LOCK_GOVERNOR, policy_dir.policy_number
+            )));
+        }
+    }
+
+    // Checked before anything is captured, since sysfs can't take the rest.
+    frequency.as_whole_khz()?;
+
+    let state = LockState::capture(backend, policy_dirs, disable_idle)?;
+    state.save(backend)?;
+
+    if let Err(e) = apply_lock(backend, policy_dirs, frequency, &state) {
+        if let Err(restore_error) = state.restore(backend, policy_dirs) {
+            return Err(CpmError::RestoreFailed {
+                error: Box::new(e),
+                restore_error: Box::new(restore_error),
+            });
+        }
+
+        remove_state(backend)?;
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Restores the state saved by lock, and removes the saved state.
+pub fn unlock<B: CpufreqBackend>(backend: &B, policy_dirs: &[PolicyDir<B>]) -> error::Result<()> {
+    let state = LockState::load(backend)?
+        .ok_or(CpmError::Unsupported("The CPU isn't locked.".to_string()))?;
+
+    state.restore(backend, policy_dirs)?;
+    remove_state(backend)
+}
+
+fn remove_state<B: CpufreqBackend>(backend: &B) -> error::Result<()> {
+    let path = Path::new(LOCK_STATE_PATH);
+
+    backend.remove(path).map_err(|e| CpmError::from_io(path, e))
+}
+
+fn apply_lock<B: CpufreqBackend>(
+    backend: &B,
+    policy_dirs: &[PolicyDir<B>],
+    frequency: &Frequency,
+    state: &LockState,
+) -> error::Result<()> {
+    // Turning boost off lowers cpuinfo_max_freq, so it goes first, and the
+    // frequency is checked against the range that's left before any policy
+    // is changed.
+    if state.boost.is_some() {
+        boost::set_boost(backend, false)?;
+    }
+
+    for policy_dir in policy_dirs {
+        let rated_min = policy_dir.read_rated_min_freq()?.as_khz();
+        let rated_max = policy_dir.read_rated_max_freq()?.as_khz();
+
+        if !(rated_min..=rated_max).contains(&frequency.as_khz()) {
+            return Err(CpmError::OutOfRange {
+                what: format!(
+                    "lock frequency (KHz) of policy {}",
+                    policy_dir.policy_number
+                ),
+                requested: frequency.as_khz(),
+                min: rated_min,
+                max: rated_max,
+            });
+        }
+    }
+
+    for policy_dir in policy_dirs {
+        policy_dir.set_governor(LOCK_GOVERNOR)?;
+
+        if policy_dir.has_file(&attributes::ENERGY_PERFORMANCE_PREFERENCE)
+            && policy_dir
+                .read_available_perf_profiles()?
+                .iter()
+                .any(|p| p == LOCK_PERF_PROFILE)
+        {
+            policy_dir.set_perf_profile(LOCK_PERF_PROFILE)?;
+        }
+
+        policy_dir.set_scaling_range(Some(frequency), Some(frequency))?;
+    }
+
+    for idle in &state.idle_states {
+        cpuidle::write_disable(backend, idle.cpu, &idle.state, "1")?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use crate::{
+    backend::CpufreqBackend,
+    error::{self, CpmError},
+    suggest, PLATFORM_PROFILE_CHOICES_PATH, PLATFORM_PROFILE_PATH,
+};
+
+use std::path::Path;
+
+/// Returns the active ACPI platform profile, e.g. balanced or quiet.
+pub fn read_platform_profile<B: CpufreqBackend>(backend: &B) -> error::Result<String> {
+    read(backend, PLATFORM_PROFILE_PATH).map(|content| content.trim().to_string())
+}
+
+/// Returns the platform profiles the firmware supports.
+pub fn read_platform_profile_choices<B: CpufreqBackend>(backend: &B) -> error::Result<Vec<String>> {
+    let choices: Vec<String> = read(backend, PLATFORM_PROFILE_CHOICES_PATH)?
+        .split_whitespace()
+        .map(|s| s.to_string())
+        .collect();
+
+    if choices.is_empty() {
+        return Err(CpmError::Unsupported(
+            "The list of platform profile choices was empty.".to_string(),
+        ));
+    }
+
+    Ok(choices)
+}
+
+/// Sets the ACPI platform profile, if it's one of the supported choices.
+pub fn set_platform_profile<B: CpufreqBackend>(backend: &B, profile: &str) -> error::Result<()> {
+    let choices = read_platform_profile_choices(backend)?;
+
+    if !choices.iter().any(|c| c == profile) {
+        return Err(CpmError::Unsupported(format!(
+            "The platform profile '{}' isn't supported.{}\nSupported profiles: {:?}",
+            profile,
+            suggest::did_you_mean(profile, choices.iter().map(String::as_str)),
+            choices
+        )));
+    }
+
+    backend
+        .write(Path::new(PLATFORM_PROFILE_PATH), profile)
+        .map_err(|e| CpmError::from_io(Path::new(PLATFORM_PROFILE_PATH), e))
+}
+
+fn read<B: CpufreqBackend>(backend: &B, path: &str) -> error::Result<String> {
+    if !backend.is_file(Path::new(path)) {
+        return Err(CpmError::Unsupported(format!(
+            "The firmware doesn't support ACPI platform profiles, '{}' doesn't exist.",
+            path
+        )));
+    }
+
+    backend
+        .read(Path::new(path))
+        .map_err(|e| CpmError::from_io(Path::new(path), e))
+}
@@ -1,90 +1,145 @@
-use crate::{frequency::Frequency, CPU_FREQ_PATH};
+use crate::{
+    attributes::{self, Attribute, Validation, Value},
+    backend::{CpufreqBackend, Recording, Sysfs},
+    epb,
+    error::{self, CpmError},
+    frequency::{Frequency, Snap},
+    suggest,
+};
 
-use strum_macros::AsRefStr;
-
-use anyhow::{self as ah, bail};
-use std::fs::{self, read_dir, DirEntry};
+use std::io::ErrorKind;
 
 use std::path::{Path, PathBuf};
 
-/// Enum representing the policy files present in a policy directory, e.g.
-/// policy0/scaling_max_freq, or policy1/scaling_governor. These are used by
-/// the PolicyDir struct to specify what policy file to read or alter.
-
-// The #[allow(non_camel_case_types)] attribute is used to suppress the warning
-// that the enum variants are not camel case. This is because the variants are
-// named after the policy files, and we're converting them to strings / paths.
-
-#[allow(non_camel_case_types)]
-#[derive(Debug, Clone, AsRefStr)]
-pub enum PolicyFile {
-    affected_cpus,
-    base_frequency,
-    cpuinfo_max_freq,
-    cpuinfo_min_freq,
-    cpuinfo_transition_latency,
-    energy_performance_available_preferences,
-    energy_performance_preference,
-    related_cpus,
-    scaling_available_governors,
-    scaling_cur_freq,
-    scaling_driver,
-    scaling_governor,
-    scaling_max_freq,
-    scaling_min_freq,
-    scaling_setspeed,
+/// The raw EPP values intel_pstate writes for each named preference with HWP.
+/// Some CPU models tune balance_performance, so these are the nominal values.
+pub const EPP_VALUES: [(&str, u8); 4] = [
+    ("performance", 0),
+    ("balance_performance", 128),
+    ("balance_power", 192),
+    ("power", 255),
+];
+
+/// Parses a raw EPP value, which must be from 0 to 255.
+pub fn parse_epp_value(value: &str) -> error::Result<u8> {
+    value.trim().parse::<u8>().map_err(|_| {
+        CpmError::Parse(format!(
+            "Invalid EPP value '{}'. Raw values must be from 0 to 255.",
+            value.trim()
+        ))
+    })
+}
+
+/// Returns whether a file reads back as what was written to it. intel_pstate
+/// reads EPP back by name when the raw value matches a named preference, so
+/// EPP is compared by raw value where both sides have one. default reads back
+/// as whatever it resolved to, and a raw write may read back as
+/// balance_performance, whose raw value some CPU models tune.
+fn reads_back_as(path: &Path, written: &str, actual: &str) -> bool {
+    let (written, actual) = (written.trim(), actual.trim());
+
+    if written == actual {
+        return true;
+    }
+
+    if !path.ends_with(attributes::ENERGY_PERFORMANCE_PREFERENCE.file_name) {
+        return false;
+    }
+
+    let raw_epp = |value: &str| {
+        EPP_VALUES
+            .iter()
+            .find(|(name, _)| *name == value)
+            .map(|(_, epp)| *epp)
+            .or_else(|| value.parse::<u8>().ok())
+    };
+
+    let Some(actual_epp) = raw_epp(actual) else {
+        return false;
+    };
+
+    match written {
+        "default" => true,
+        _ if actual == "balance_performance" => written.parse::<u8>().is_ok(),
+        _ => raw_epp(written) == Some(actual_epp),
+    }
+}
+
+/// Returns the named preference whose raw EPP value is closest to the value.
+pub fn nearest_epp_name(value: u8) -> &'static str {
+    EPP_VALUES
+        .iter()
+        .min_by_key(|(_, v)| v.abs_diff(value))
+        .map(|(name, _)| *name)
+        .unwrap_or("performance")
 }
 
-pub struct PolicyDir {
+pub struct PolicyDir<B: CpufreqBackend = Sysfs> {
     pub full_path: PathBuf,
     pub dir_name: String,
     pub policy_number: u32,
+    /// Every file of the policy is read and written through the backend.
+    pub backend: B,
 }
 
-/// Generates methods for reading frequency values in KHz from PolicyFiles,
-/// and converts them to Frequency enum variants.
-
+/// Generates methods for reading frequency values in KHz from attributes,
+/// and converts them to Frequency values.
 macro_rules! generate_frequency_readers {
-    ($($method_name:ident, $file:ident)+) => {
+    ($($method_name:ident, $attribute:ident)+) => {
         $(
-            pub fn $method_name(&self) -> ah::Result<Frequency> {
-                let content = self.read(PolicyFile::$file)?;
-                let parsed_num = content.trim().parse::<u64>()?;
-                let frequency = Frequency::KHz(parsed_num);
+            pub fn $method_name(&self) -> error::Result<Frequency> {
+                let content = self.read(&attributes::$attribute)?;
+                let parsed_num = content.trim().parse::<u64>().map_err(|_| {
+                    CpmError::Parse(format!(
+                        "Invalid frequency '{}' in {}.",
+                        content.trim(),
+                        attributes::$attribute.file_name
+                    ))
+                })?;
+                let frequency = Frequency::from_khz(parsed_num);
                 Ok(frequency)
             }
         )+
     };
 }
 
-impl PolicyDir {
+impl PolicyDir<Sysfs> {
+    /// Creates a PolicyDir for a policy directory in sysfs. See with_backend.
+    pub fn from(path: &str) -> error::Result<Self> {
+        Self::with_backend(path, Sysfs)
+    }
+
+    /// Collects all policy directories in sysfs from the provided path.
+    pub fn collect_from_dir(path: &str) -> error::Result<Vec<Self>> {
+        Self::collect_with_backend(path, Sysfs)
+    }
+}
+
+impl<B: CpufreqBackend> PolicyDir<B> {
     /// Creates a new PolicyDir struct from a path pointing to a policy directory.
     /// The policy directory name is expected to be in the format "policyN", where
     /// N is the number associated with the CPU core the policy governs. This
     /// function makes no garantuees about the presence of the expected policy
     /// files. Just because the function returns a PolicyDir struct doesn't mean
     /// the policy directory contains the expected policy files files.
-
-    pub fn from(path: &str) -> ah::Result<Self> {
+    pub fn with_backend(path: &str, backend: B) -> error::Result<Self> {
         let path_str = path;
         let path = Path::new(&path_str);
 
-        if !path.exists() {
-            ah::bail!("The provided path '{}' doesn't exist.", path.display());
-        }
-
-        if !path.is_dir() {
-            ah::bail!("The provided path '{}' wasn't a directory.", path.display());
+        if !backend.is_dir(path) {
+            return Err(CpmError::NotFound {
+                path: path.to_path_buf(),
+            });
         }
 
         let full_path = path.to_path_buf();
 
         let dir_name = path
             .file_name()
-            .ok_or(ah::anyhow!(
+            .ok_or(CpmError::Parse(format!(
                 "The directory name of path '{}' could not be retrieved.",
                 path.display()
-            ))?
+            )))?
             .to_string_lossy()
             .to_string();
 
@@ -93,37 +148,42 @@ impl PolicyDir {
         let policy_number = name_trimmed
             .chars()
             .all(|c| c.is_ascii_digit())
-            .then(|| name_trimmed.parse::<u32>())
-            .ok_or(ah::anyhow!(
+            .then(|| name_trimmed.parse::<u32>().ok())
+            .flatten()
+            .ok_or(CpmError::Parse(format!(
                 "The policy number '{:?}' couldn't be parsed as a u32.",
                 name_trimmed
-            ))??;
+            )))?;
 
         Ok(Self {
             full_path,
             dir_name,
             policy_number,
+            backend,
         })
     }
 
-    /// Collects all policy directories from the provided path.
-    pub fn collect_from_dir(path: &str) -> ah::Result<Vec<Self>> {
+    /// Collects all policy directories from the provided path, each of them
+    /// accessed through its own copy of the backend.
+    pub fn collect_with_backend(path: &str, backend: B) -> error::Result<Vec<Self>>
+    where
+        B: Clone,
+    {
         let path = Path::new(path);
 
-        if !path.exists() {
-            bail!("The provided path '{}' doesn't exist.", path.display());
-        }
-
-        if !path.is_dir() {
-            bail!("The provided path '{}' wasn't a directory.", path.display());
+        if !backend.is_dir(path) {
+            return Err(CpmError::NotFound {
+                path: path.to_path_buf(),
+            });
         }
 
-        let entries: Vec<DirEntry> = read_dir(path)?.collect::<ah::Result<Vec<_>, std::io::Error>>()?;
+        let entries = backend
+            .read_dir(path)
+            .map_err(|e| CpmError::from_io(path, e))?;
 
         let policy_dir_paths: Vec<String> = entries
             .into_iter()
-            .filter_map(|entry| {
-                let entry_path = entry.path();
+            .filter_map(|entry_path| {
                 let entry_name = entry_path.file_name()?.to_string_lossy().to_string();
 
                 if !entry_name.starts_with("policy") {
@@ -144,20 +204,72 @@ impl PolicyDir {
 
         policy_dir_paths
             .into_iter()
-            .map(|path| Self::from(&path)) // Assuming Self::from returns ah::Result<Self, _>
-            .collect::<ah::Result<Vec<Self>, _>>()
+            .map(|path| Self::with_backend(&path, backend.clone()))
+            .collect()
+    }
+
+    /// Returns a view of the policy whose writes are checked and recorded, but
+    /// not made, so a set of changes can be validated before any is applied.
+    pub fn dry_run(&self) -> PolicyDir<Recording<&B>> {
+        PolicyDir {
+            full_path: self.full_path.clone(),
+            dir_name: self.dir_name.clone(),
+            policy_number: self.policy_number,
+            backend: Recording::new(&self.backend),
+        }
+    }
+
+    /// Makes writes recorded by a dry run, in the order they were recorded.
+    /// Every write is verified like write does.
+    pub fn replay(&self, writes: &[(PathBuf, String)]) -> error::Result<()> {
+        for (path, contents) in writes {
+            self.write_path(path, contents)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the CPUs governed by this policy, from the affected_cpus file.
+    /// A policy whose CPUs are all offline is inactive, and the kernel refuses
+    /// reads of its files, so it's reported as having no CPUs.
+    pub fn read_affected_cpus(&self) -> error::Result<Vec<u32>> {
+        let path = self.full_path.join(attributes::AFFECTED_CPUS.file_name);
+
+        let content = match self.backend.read(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::ResourceBusy => return Ok(Vec::new()),
+            Err(_) => self.read(&attributes::AFFECTED_CPUS)?,
+        };
+
+        content
+            .split_whitespace()
+            .map(|cpu| {
+                cpu.parse::<u32>().map_err(|_| {
+                    CpmError::Parse(format!(
+                        "Invalid CPU number '{}' in affected_cpus of policy: {}",
+                        cpu,
+                        self.full_path.display()
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// Returns whether any CPU governed by this policy is online.
+    pub fn is_active(&self) -> bool {
+        self.read_affected_cpus().is_ok_and(|cpus| !cpus.is_empty())
     }
 
     /// Returns the available governors from the scaling_available_governors file.
-    pub fn read_available_governors(&self) -> ah::Result<Vec<String>> {
-        let content = self.read(PolicyFile::scaling_available_governors)?;
+    pub fn read_available_governors(&self) -> error::Result<Vec<String>> {
+        let content = self.read(&attributes::SCALING_AVAILABLE_GOVERNORS)?;
         let governors: Vec<String> = content.split_whitespace().map(|s| s.to_string()).collect();
 
         if governors.is_empty() {
-            ah::bail!(
+            return Err(CpmError::Unsupported(format!(
                 "The list of governors was empty for policy: {}",
                 self.full_path.display()
-            );
+            )));
         }
 
         Ok(governors)
@@ -165,37 +277,27 @@ impl PolicyDir {
 
     /// Sets the scaling_governor to the desired governor, if the governor is
     /// in the list of available governors given by scaling_available_governors.
-    pub fn set_governor(&self, governor: &str) -> ah::Result<()> {
-        let available = self.read_available_governors()?;
-
-        if !available.contains(&governor.to_string()) {
-            ah::bail!(
-                "The governor '{}' isn't supported.\nSupported governors: {:?}'",
-                governor,
-                available
-            );
-        }
-
-        self.write(PolicyFile::scaling_governor, governor)
+    pub fn set_governor(&self, governor: &str) -> error::Result<()> {
+        self.set_value(&attributes::SCALING_GOVERNOR, governor)
     }
 
     /// Returns the current governor from the scaling_governor file.
-    pub fn read_current_governor(&self) -> ah::Result<String> {
-        self.read(PolicyFile::scaling_governor)
+    pub fn read_current_governor(&self) -> error::Result<String> {
+        self.read(&attributes::SCALING_GOVERNOR)
     }
 
     /// Returns the available governors from the scaling_available_governors file.
-    pub fn read_available_perf_profiles(&self) -> ah::Result<Vec<String>> {
-        let content = self.read(PolicyFile::energy_performance_available_preferences)?;
+    pub fn read_available_perf_profiles(&self) -> error::Result<Vec<String>> {
+        let content = self.read(&attributes::ENERGY_PERFORMANCE_AVAILABLE_PREFERENCES)?;
 
         let perf_profiles: Vec<String> =
             content.split_whitespace().map(|s| s.to_string()).collect();
 
         if perf_profiles.is_empty() {
-            ah::bail!(
+            return Err(CpmError::Unsupported(format!(
                 "The list of performance profiles was empty for policy: {}",
                 self.full_path.display()
-            );
+            )));
         }
 
         Ok(perf_profiles)
@@ -203,223 +305,420 @@ impl PolicyDir {
 
     /// Sets the performance profile to the desired profile, if the profile is
     /// in the list of available power profiles given by read_available_perf_profiles.
-    pub fn set_perf_profile(&self, profile: &str) -> ah::Result<()> {
-        let available = self.read_available_perf_profiles()?;
-
-        if !available.contains(&profile.to_string()) {
-            ah::bail!(
-                "The performance profile '{}' can't be found.\nAvailable profiles: {:?}",
-                profile,
-                available
-            );
-        }
-
-        self.write(PolicyFile::energy_performance_preference, profile)
+    /// With HWP, a raw EPP value from 0 to 255 is also accepted.
+    pub fn set_perf_profile(&self, profile: &str) -> error::Result<()> {
+        self.set_value(&attributes::ENERGY_PERFORMANCE_PREFERENCE, profile)
     }
 
     /// Returns the current performance profile from the energy_performance_preference file.
-    pub fn read_current_perf_profile(&self) -> ah::Result<String> {
-        self.read(PolicyFile::energy_performance_preference)
+    pub fn read_current_perf_profile(&self) -> error::Result<String> {
+        self.read(&attributes::ENERGY_PERFORMANCE_PREFERENCE)
     }
 
-    // Boilerplate reduction; the parsing and conversion logic is identical.
-    generate_frequency_readers!(
-        read_base_frequency,   base_frequency
-        read_rated_max_freq,   cpuinfo_max_freq
-        read_rated_min_freq,   cpuinfo_min_freq
-        read_current_freq,     scaling_cur_freq
-        read_scaling_max_freq, scaling_max_freq
-        read_scaling_min_freq, scaling_min_freq
-    );
-
-    /// Sets the scaling_max_freq to the desired frequency, if the desired
-    /// frequency falls within the rated min and max rated frequencies.
-    pub fn set_scaling_max_freq(&self, frequency: &Frequency) -> ah::Result<()> {
-        let desired_scaling_max = frequency.to_khz();
+    /// Returns the current performance profile as a raw EPP value, along with
+    /// the named preference it equals or is nearest to. The kernel reports the
+    /// name when the value matches one, and the raw number otherwise.
+    pub fn read_current_epp_value(&self) -> error::Result<(u8, &'static str)> {
+        let profile = self.read_current_perf_profile()?;
+        let profile = profile.trim();
 
-        // Desired scaling_max frequency cannot be lower than the rated_min.
-        let rated_min = self.read_rated_min_freq()?.to_khz();
+        let value = match EPP_VALUES.iter().find(|(name, _)| *name == profile) {
+            Some((_, value)) => *value,
+            None => parse_epp_value(profile)?,
+        };
 
-        if u64::from(desired_scaling_max) < u64::from(rated_min) {
-            ah::bail!(
-                "The desired scaling_max frequency '{}' is lower than the rated minimum '{}'",
-                desired_scaling_max.to_ghz(),
-                rated_min.to_ghz()
-            );
-        }
+        Ok((value, nearest_epp_name(value)))
+    }
 
-        // Desired scaling_max frequency cannot be higher than the rated max.
-        let max_rated = self.read_rated_max_freq()?.to_khz();
+    /// Returns the energy performance bias of every CPU in affected_cpus.
+    pub fn read_energy_perf_bias(&self) -> error::Result<Vec<(u32, u8)>> {
+        self.read_affected_cpus()?
+            .into_iter()
+            .map(|cpu| Ok((cpu, epb::read_epb(&self.backend, cpu)?)))
+            .collect()
+    }
 
-        if u64::from(desired_scaling_max) > u64::from(max_rated) {
-            ah::bail!(
-                "The desired scaling_max frequency '{}' is higher than the maximum rated frequency '{}'",
-                desired_scaling_max.to_ghz(),
-                max_rated.to_ghz()
-            );
+    /// Sets the energy performance bias of every CPU in affected_cpus.
+    pub fn set_energy_perf_bias(&self, bias: u8) -> error::Result<()> {
+        if bias > epb::EPB_MAX {
+            return Err(CpmError::OutOfRange {
+                what: "energy performance bias".to_string(),
+                requested: bias.into(),
+                min: 0,
+                max: epb::EPB_MAX.into(),
+            });
         }
 
-        // Desired scaling_max frequency cannot be lower than the rated min.
-        let min_rated = self.read_rated_min_freq()?.to_khz();
-
-        if u64::from(desired_scaling_max) < u64::from(min_rated) {
-            ah::bail!(
-                "The desired scaling_max frequency '{}' is lower than the minimum rated frequency '{}'",
-                desired_scaling_max.to_ghz(),
-                min_rated.to_ghz()
-            );
+        for cpu in self.read_affected_cpus()? {
+            epb::set_epb(&self.backend, cpu, bias)?;
         }
 
-        self.write(
-            PolicyFile::scaling_max_freq,
-            &desired_scaling_max.to_khz().to_string_u64(),
-        )?;
-
         Ok(())
     }
 
-    /// Sets the scaling_min_freq to the desired frequency, if the desired
-    /// frequency falls within the rated min and max rated frequencies.
-    pub fn set_scaling_min_freq(&self, frequency: &Frequency) -> ah::Result<()> {
-        let desired_scaling_min = frequency.to_khz();
-
-        // Desired scaling_min frequency cannot be higher than the scaling_max.
-        let scaling_max = self.read_scaling_max_freq()?.to_khz();
+    // Boilerplate reduction; the parsing and conversion logic is identical.
+    generate_frequency_readers!(
+        read_base_frequency,   BASE_FREQUENCY
+        read_rated_max_freq,   CPUINFO_MAX_FREQ
+        read_rated_min_freq,   CPUINFO_MIN_FREQ
+        read_bios_limit,       BIOS_LIMIT
+        read_current_freq,     SCALING_CUR_FREQ
+        read_scaling_max_freq, SCALING_MAX_FREQ
+        read_scaling_min_freq, SCALING_MIN_FREQ
+    );
 
-        if u64::from(desired_scaling_min) > u64::from(scaling_max) {
-            ah::bail!(
-                "The desired scaling_min frequency '{}' is higher than the current scaling_max '{}'",
-                desired_scaling_min.to_ghz(),
-                scaling_max.to_ghz()
-            );
+    /// Returns the discrete frequency steps from scaling_available_frequencies,
+    /// lowest first. Only some drivers, e.g. acpi-cpufreq, have steps, so the
+    /// list is empty when the file doesn't exist.
+    pub fn read_available_frequencies(&self) -> error::Result<Vec<Frequency>> {
+        if !self.has_file(&attributes::SCALING_AVAILABLE_FREQUENCIES) {
+            return Ok(Vec::new());
         }
 
-        // Desired scaling_min frequency cannot be lower than the rated min.
-        let min_rated = self.read_rated_min_freq()?.to_khz();
+        let content = self.read(&attributes::SCALING_AVAILABLE_FREQUENCIES)?;
+
+        let mut steps = content
+            .split_whitespace()
+            .map(|step| {
+                step.parse::<u64>().map(Frequency::from_khz).map_err(|_| {
+                    CpmError::Parse(format!(
+                        "Invalid frequency '{}' in scaling_available_frequencies of policy: {}",
+                        step,
+                        self.full_path.display()
+                    ))
+                })
+            })
+            .collect::<error::Result<Vec<Frequency>>>()?;
 
-        if u64::from(desired_scaling_min) < u64::from(min_rated) {
-            ah::bail!(
-                "The desired scaling_min frequency '{}' is higher than the minimum rated frequency '{}'",
-                desired_scaling_min.to_ghz(),
-                min_rated.to_ghz()
-            );
-        }
+        steps.sort();
 
-        // Desired scaling_min frequency cannot be higher than the rated max.
-        let max_rated = self.read_rated_max_freq()?.to_khz();
+        Ok(steps)
+    }
 
-        if u64::from(desired_scaling_min) > u64::from(max_rated) {
-            ah::bail!(
-                "The desired scaling_min frequency '{}' is higher than the maximum rated frequency '{}'",
-                desired_scaling_min.to_ghz(),
-                max_rated.to_ghz()
-            );
-        }
+    /// Returns the step the frequency snaps to, or the frequency itself when
+    /// the policy has no discrete steps.
+    pub fn resolve_frequency(&self, frequency: &Frequency, snap: Snap) -> error::Result<Frequency> {
+        let steps = self.read_available_frequencies()?;
+        Ok(frequency.snap(&steps, snap).unwrap_or(*frequency))
+    }
 
-        self.write(
-            PolicyFile::scaling_min_freq,
-            &desired_scaling_min.to_khz().to_string_u64(),
-        )?;
+    /// Sets the scaling_max_freq to the desired frequency, keeping the
+    /// scaling_min_freq. See set_scaling_range.
+    pub fn set_scaling_max_freq(&self, frequency: &Frequency) -> error::Result<()> {
+        self.set_scaling_range(None, Some(frequency))
+    }
 
-        Ok(())
+    /// Sets the scaling_min_freq to the desired frequency, keeping the
+    /// scaling_max_freq. See set_scaling_range.
+    pub fn set_scaling_min_freq(&self, frequency: &Frequency) -> error::Result<()> {
+        self.set_scaling_range(Some(frequency), None)
     }
 
-    /// Generic method for reading from a policy file.
-    pub fn read(&self, policy_file: PolicyFile) -> ah::Result<String> {
-        let file_name = policy_file.as_ref();
-        let path = self.full_path.join(file_name);
+    /// Sets scaling_min_freq and scaling_max_freq together. Either may be left
+    /// out to keep its current value. The pair is validated against the rated
+    /// range and each other before anything is written, then written in the
+    /// order that keeps min <= max at every moment: min first when the new
+    /// min doesn't exceed the current max, otherwise max first.
+    pub fn set_scaling_range(
+        &self,
+        min: Option<&Frequency>,
+        max: Option<&Frequency>,
+    ) -> error::Result<()> {
+        let rated_min = self.read_rated_min_freq()?.as_khz();
+        let rated_max = self.read_rated_max_freq()?.as_khz();
+        let current_max = self.read_scaling_max_freq()?.as_khz();
+
+        let desired_max = match max {
+            Some(max) => max.as_whole_khz()?,
+            None => current_max,
+        };
+
+        let desired_min = match min {
+            Some(min) => min.as_whole_khz()?,
+            None => self.read_scaling_min_freq()?.as_khz(),
+        };
+
+        // A max given on its own can't go below the current min.
+        let lowest_max = match min {
+            Some(_) => rated_min,
+            None => desired_min.max(rated_min),
+        };
+
+        if max.is_some() && !(lowest_max..=rated_max).contains(&desired_max) {
+            return Err(CpmError::OutOfRange {
+                what: "scaling_max frequency (KHz)".to_string(),
+                requested: desired_max,
+                min: lowest_max,
+                max: rated_max,
+            });
+        }
 
-        if !path.is_file() {
-            return Err(ah::anyhow!(
-                "The policy file '{}' doesn't exist. Couldn't read from it.",
-                path.display()
-            ));
+        if !(rated_min..=desired_max).contains(&desired_min) {
+            return Err(CpmError::OutOfRange {
+                what: "scaling_min frequency (KHz)".to_string(),
+                requested: desired_min,
+                min: rated_min,
+                max: desired_max,
+            });
         }
 
-        std::fs::read_to_string(&path).map_err(|e| {
-            ah::anyhow!(
-                "Coudln't read policy file '{}' due to error '{}'",
-                path.display(),
-                e
-            )
-        })
+        let write_min = || match min {
+            Some(_) => self.write(&attributes::SCALING_MIN_FREQ, &desired_min.to_string()),
+            None => Ok(()),
+        };
+
+        let write_max = || match max {
+            Some(_) => self.write(&attributes::SCALING_MAX_FREQ, &desired_max.to_string()),
+            None => Ok(()),
+        };
+
+        if desired_min <= current_max {
+            write_min()?;
+            write_max()
+        } else {
+            write_max()?;
+            write_min()
+        }
     }
 
-    /// Generic method for writing to a policy file.
-    pub fn write(&self, policy_file: PolicyFile, contents: &str) -> ah::Result<()> {
-        let file_name = policy_file.as_ref();
-        let path = self.full_path.join(file_name);
+    /// Returns whether the policy file is present in this policy directory.
+    pub fn has_file(&self, attribute: &Attribute) -> bool {
+        self.backend
+            .is_file(&self.full_path.join(attribute.file_name))
+    }
 
-        if !path.is_file() {
-            return Err(ah::anyhow!(
-                "The policy file '{}' doesn't exist. Couldn't write to it.",
-                path.display()
-            ));
+    /// Reads and parses an attribute according to its registry entry.
+    pub fn read_value(&self, attribute: &Attribute) -> error::Result<Value> {
+        attribute.parse(&self.read(attribute)?)
+    }
+
+    /// Parses a value given on the command line, validates it as the
+    /// attribute's registry entry declares, and writes it.
+    pub fn set_value(&self, attribute: &Attribute, input: &str) -> error::Result<()> {
+        if !attribute.writable {
+            return Err(CpmError::Unsupported(format!(
+                "The {} of policy {} can't be set.",
+                attribute.description, self.policy_number
+            )));
         }
 
-        fs::write(&path, contents).map_err(|e| {
-            ah::anyhow!(
-                "Couldn't write to policy file '{}' due to error '{}'",
-                path.display(),
-                e
-            )
-        })
+        let value = attribute.parse_input(input)?;
+        self.validate_value(attribute, &value)?;
+        self.write(attribute, &value.to_file_string()?)
     }
 
-    /// Return paths to each policy0, policy1, policy2.. directory in CPU_FREQ_PATH.
-    pub fn get_policy_dirs() -> ah::Result<Vec<(String, String)>> {
-        let mut policy_dirs = Vec::<(String, String)>::new();
+    /// Checks a value against the attribute's validation rule.
+    pub fn validate_value(&self, attribute: &Attribute, value: &Value) -> error::Result<()> {
+        match (attribute.validation, value) {
+            (Validation::None, _) => Ok(()),
+
+            (Validation::RatedRange, Value::Frequency(frequency)) => {
+                let desired = frequency.as_whole_khz()?;
+                let rated_min = self.read_rated_min_freq()?.as_khz();
+                let rated_max = self.read_rated_max_freq()?.as_khz();
+
+                if !(rated_min..=rated_max).contains(&desired) {
+                    return Err(CpmError::OutOfRange {
+                        what: format!("{} (KHz)", attribute.description),
+                        requested: desired,
+                        min: rated_min,
+                        max: rated_max,
+                    });
+                }
 
-        for entry in read_dir(CPU_FREQ_PATH)? {
-            let entry = entry?;
-            let path = entry.path();
+                Ok(())
+            }
 
-            if !path.is_dir() {
-                continue;
+            (Validation::OneOfOrInt(_, max), Value::String(string))
+                if string.parse::<i64>().is_ok() =>
+            {
+                let requested = string.parse::<i64>().unwrap_or_default();
+
+                if !(0..=max).contains(&requested) {
+                    return Err(CpmError::OutOfRange {
+                        what: attribute.description.to_string(),
+                        requested: requested.unsigned_abs(),
+                        min: 0,
+                        max: max.unsigned_abs(),
+                    });
+                }
+
+                Ok(())
             }
 
-            let dir_name = match path.file_name() {
-                Some(name) => name.to_string_lossy().to_string(),
-                None => continue,
-            };
+            (Validation::OneOf(choices) | Validation::OneOfOrInt(choices, _), value) => {
+                let Value::List(available) = self.read_value(choices)? else {
+                    return Err(CpmError::Parse(format!(
+                        "The {} of policy {} isn't a list.",
+                        choices.description, self.policy_number
+                    )));
+                };
+
+                let desired = value.to_file_string()?;
+
+                if !available.contains(&desired) {
+                    return Err(CpmError::Unsupported(format!(
+                        "'{}' is not one of the {} of policy {}.{}\nAvailable: {:?}",
+                        desired,
+                        choices.description,
+                        self.policy_number,
+                        suggest::did_you_mean(&desired, available.iter().map(String::as_str)),
+                        available
+                    )));
+                }
 
-            if !dir_name.starts_with("policy") {
-                continue;
+                Ok(())
             }
 
-            policy_dirs.push((dir_name, path.to_string_lossy().to_string()));
+            (Validation::RatedRange, _) => Err(CpmError::Parse(format!(
+                "The {} must be a frequency.",
+                attribute.description
+            ))),
+        }
+    }
+
+    /// Generic method for reading from a policy file.
+    pub fn read(&self, attribute: &Attribute) -> error::Result<String> {
+        self.read_file(attribute.file_name)
+    }
+
+    /// Generic method for writing to a policy file. The file is read back
+    /// afterwards, failing with NotApplied if the kernel didn't keep the value.
+    pub fn write(&self, attribute: &Attribute, contents: &str) -> error::Result<()> {
+        self.write_file(attribute.file_name, contents)
+    }
+
+    /// Returns the name of every file in the policy directory, including the
+    /// ones the attribute registry doesn't declare, and whether its mode bits allow writing.
+    pub fn list_raw_files(&self) -> error::Result<Vec<(String, bool)>> {
+        let mut files: Vec<(String, bool)> = self
+            .backend
+            .read_dir(&self.full_path)
+            .map_err(|e| self.map_io_error(&self.full_path, e))?
+            .into_iter()
+            .filter(|path| self.backend.is_file(path))
+            .filter_map(|path| {
+                let file_name = path.file_name()?.to_string_lossy().to_string();
+                Some((file_name, self.backend.is_writable(&path)))
+            })
+            .collect();
+
+        if files.is_empty() {
+            return Err(CpmError::Unsupported("No policy files found!".to_string()));
         }
 
-        if policy_dirs.is_empty() {
-            return Err(ah::anyhow!("No policy folders found!"));
+        files.sort();
+
+        Ok(files)
+    }
+
+    /// Reads any file in the policy directory by name. Only names listed by
+    /// list_raw_files are accepted, so paths can't escape the directory.
+    pub fn read_raw(&self, file_name: &str) -> error::Result<String> {
+        self.find_raw_file(file_name)?;
+        self.read_file(file_name)
+    }
+
+    /// Writes any writable file in the policy directory by name.
+    pub fn write_raw(&self, file_name: &str, contents: &str) -> error::Result<()> {
+        if !self.find_raw_file(file_name)? {
+            return Err(CpmError::PermissionDenied {
+                path: self.full_path.join(file_name),
+            });
         }
 
-        Ok(policy_dirs)
+        self.write_file(file_name, contents)
     }
 
-    pub fn get_policy_files(policy_dir: &str) -> ah::Result<Vec<(String, String)>> {
-        let mut policy_files = Vec::<(String, String)>::new();
+    /// Returns whether the named file is writable, or an error listing the
+    /// available files if there is no such file.
+    fn find_raw_file(&self, file_name: &str) -> error::Result<bool> {
+        let files = self.list_raw_files()?;
+
+        match files.iter().find(|(name, _)| name == file_name) {
+            Some((_, writable)) => Ok(*writable),
+            None => Err(CpmError::Unsupported(format!(
+                "The policy file '{}' doesn't exist in policy {}.{}\nAvailable files: {:?}",
+                file_name,
+                self.policy_number,
+                suggest::did_you_mean(file_name, files.iter().map(|(name, _)| name.as_str())),
+                files
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>()
+            ))),
+        }
+    }
 
-        for entry in read_dir(policy_dir)? {
-            let entry = entry?;
-            let path = entry.path();
+    fn read_file(&self, file_name: &str) -> error::Result<String> {
+        let path = self.full_path.join(file_name);
 
-            if !path.is_file() {
-                continue;
-            }
+        if !self.backend.is_file(&path) {
+            return Err(CpmError::NotFound { path });
+        }
 
-            let file_name = match path.file_name() {
-                Some(name) => name.to_string_lossy().to_string(),
-                None => continue,
-            };
+        self.backend
+            .read(&path)
+            .map_err(|e| self.map_io_error(&path, e))
+    }
+
+    fn write_file(&self, file_name: &str, contents: &str) -> error::Result<()> {
+        let path = self.full_path.join(file_name);
+
+        if !self.backend.is_file(&path) {
+            return Err(CpmError::NotFound { path });
+        }
+
+        self.write_path(&path, contents)
+    }
 
-            policy_files.push((file_name, path.to_string_lossy().to_string()));
+    /// Writes the file and reads it back, failing with the value it reads
+    /// back as if that differs from what was written, since the kernel
+    /// silently clamps or ignores some writes.
+    fn write_path(&self, path: &Path, contents: &str) -> error::Result<()> {
+        if let Err(e) = self.backend.write(path, contents) {
+            return Err(match self.map_io_error(path, e) {
+                CpmError::Io { source, .. } if source.kind() == ErrorKind::ResourceBusy => {
+                    CpmError::Unsupported(format!(
+                        "The kernel refused to write '{}' to '{}' with error '{}'. intel_pstate \
+                         does this e.g. for an EPP other than 'performance' under the \
+                         performance governor.",
+                        contents.trim(),
+                        path.display(),
+                        source
+                    ))
+                }
+                other => other,
+            });
         }
 
-        if policy_files.is_empty() {
-            return Err(ah::anyhow!("No policy files found!"));
+        let actual = self
+            .backend
+            .read(path)
+            .map_err(|e| self.map_io_error(path, e))?;
+
+        if !reads_back_as(path, contents, &actual) {
+            return Err(CpmError::NotApplied {
+                path: path.to_path_buf(),
+                written: contents.trim().to_string(),
+                actual: actual.trim().to_string(),
+            });
         }
 
-        Ok(policy_files)
+        Ok(())
+    }
+
+    /// Reading or writing the files of a policy whose CPUs are all offline
+    /// fails with EBUSY, which is reported as the policy being inactive. The
+    /// kernel also uses EBUSY for other refusals, so the CPUs are checked.
+    fn map_io_error(&self, path: &Path, error: std::io::Error) -> CpmError {
+        match error.kind() {
+            ErrorKind::ResourceBusy if matches!(self.read_affected_cpus().as_deref(), Ok([])) => {
+                CpmError::Inactive {
+                    policy: self.policy_number,
+                }
+            }
+            _ => CpmError::from_io(path, error),
+        }
     }
 }
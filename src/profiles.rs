@@ -0,0 +1,118 @@
+use crate::{
+    backend::CpufreqBackend,
+    error::{self, CpmError},
+    suggest, PROFILES_PATH,
+};
+
+use std::path::Path;
+
+/// The settings a profile may contain. Everything but platform and policies
+/// takes the same values as the matching `cpm set` attribute.
+pub const PROFILE_KEYS: [&str; 6] = ["platform", "policies", "freq", "gov", "perf", "epb"];
+
+/// A named set of settings applied together, e.g. a "quiet" profile setting
+/// the firmware platform profile, the governor and frequency caps at once.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub settings: Vec<(String, String)>,
+}
+
+impl Profile {
+    /// Returns the value of a setting, if the profile contains it.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.settings
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Parses profiles from an INI style file, where each [section] is a profile
+/// and each key = value line one of its settings, e.g.
+///
+/// [quiet]
+/// platform = quiet
+/// gov = powersave
+/// freq = :2.0
+///
+/// Lines starting with # are comments.
+pub fn parse_profiles(content: &str) -> error::Result<Vec<Profile>> {
+    let mut profiles: Vec<Profile> = Vec::new();
+
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            profiles.push(Profile {
+                name: name.trim().to_string(),
+                settings: Vec::new(),
+            });
+
+            continue;
+        }
+
+        let (key, value) = line.split_once('=').ok_or(CpmError::Parse(format!(
+            "Line {} of the profiles isn't a key = value pair.",
+            i + 1
+        )))?;
+
+        let key = key.trim().to_lowercase();
+
+        if !PROFILE_KEYS.contains(&key.as_str()) {
+            return Err(CpmError::Parse(format!(
+                "Unknown profile setting '{}' on line {}.{}\nValid settings: {:?}",
+                key,
+                i + 1,
+                suggest::did_you_mean(&key, PROFILE_KEYS),
+                PROFILE_KEYS
+            )));
+        }
+
+        let profile = profiles.last_mut().ok_or(CpmError::Parse(format!(
+            "Line {} of the profiles comes before any [profile].",
+            i + 1
+        )))?;
+
+        profile.settings.push((key, value.trim().to_string()));
+    }
+
+    Ok(profiles)
+}
+
+/// Loads the profiles from PROFILES_PATH.
+pub fn load_profiles<B: CpufreqBackend>(backend: &B) -> error::Result<Vec<Profile>> {
+    let path = Path::new(PROFILES_PATH);
+
+    if !backend.is_file(path) {
+        return Err(CpmError::Unsupported(format!(
+            "No profiles are defined, '{}' doesn't exist.",
+            PROFILES_PATH
+        )));
+    }
+
+    let content = backend.read(path).map_err(|e| CpmError::from_io(path, e))?;
+
+    parse_profiles(&content)
+}
+
+/// Loads the profile with the given name from PROFILES_PATH.
+pub fn load_profile<B: CpufreqBackend>(backend: &B, name: &str) -> error::Result<Profile> {
+    let profiles = load_profiles(backend)?;
+    let names: Vec<String> = profiles.iter().map(|p| p.name.clone()).collect();
+
+    profiles
+        .into_iter()
+        .find(|p| p.name == name)
+        .ok_or(CpmError::Unsupported(format!(
+            "The profile '{}' isn't defined in '{}'.{}\nDefined profiles: {:?}",
+            name,
+            PROFILES_PATH,
+            suggest::did_you_mean(name, names.iter().map(String::as_str)),
+            names
+        )))
+}
@@ -0,0 +1,506 @@
+use crate::{
+    backend::{CpufreqBackend, InMemory},
+    error::{self, CpmError},
+};
+
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The governors every simulated policy offers.
+pub const SIMULATED_GOVERNORS: [&str; 6] = [
+    "performance",
+    "powersave",
+    "userspace",
+    "ondemand",
+    "conservative",
+    "schedutil",
+];
+
+/// The scaling driver name reported by simulated policies.
+pub const SIMULATED_DRIVER: &str = "cpm-sim";
+
+/// The load above which ondemand jumps straight to the max frequency.
+const ONDEMAND_UP_THRESHOLD: f64 = 0.8;
+
+/// How far conservative moves per step, as a fraction of the max frequency.
+const CONSERVATIVE_FREQ_STEP: f64 = 0.05;
+
+/// The hardware a simulated policy describes. Frequencies are in KHz.
+#[derive(Debug, Clone)]
+pub struct SimulatedPolicy {
+    pub cpus: Vec<u32>,
+    pub rated_min: u64,
+    pub rated_max: u64,
+    /// The frequencies the hardware can run at, listed in
+    /// scaling_available_frequencies. Every written or chosen frequency is
+    /// snapped to one of these.
+    pub frequencies: Vec<u64>,
+}
+
+impl SimulatedPolicy {
+    /// A policy with one frequency step every 100 MHz between min and max.
+    pub fn new(cpus: Vec<u32>, rated_min: u64, rated_max: u64) -> Self {
+        let frequencies = (rated_min..=rated_max).step_by(100_000).collect();
+
+        Self {
+            cpus,
+            rated_min,
+            rated_max,
+            frequencies,
+        }
+    }
+}
+
+/// One step of a load trace, holding the load of every policy, from 0.0 to
+/// 1.0, for a duration. A single load applies to every policy.
+#[derive(Debug, Clone)]
+pub struct LoadSample {
+    pub duration: Duration,
+    pub loads: Vec<f64>,
+}
+
+/// A synthetic load trace, replayed by Simulator::run.
+#[derive(Debug, Clone, Default)]
+pub struct LoadTrace {
+    pub samples: Vec<LoadSample>,
+}
+
+impl LoadTrace {
+    /// Parses a trace where each line is a duration in milliseconds, followed
+    /// by either one load for every policy or a load per policy. Loads are
+    /// fractions from 0.0 to 1.0, or percentages when suffixed with %. Empty
+    /// lines and lines starting with # are ignored.
+    ///
+    /// ```text
+    /// # ms   policy0 policy1
+    /// 500    10%     0.1
+    /// 1000   100%    0.5
+    /// ```
+    pub fn parse(content: &str) -> error::Result<Self> {
+        let mut samples = Vec::new();
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |what: &str| {
+                CpmError::Parse(format!(
+                    "Invalid {} on line {} of load trace: '{}'",
+                    what,
+                    i + 1,
+                    line
+                ))
+            };
+
+            let mut fields = line.split_whitespace();
+
+            let duration = fields
+                .next()
+                .and_then(|ms| ms.parse::<u64>().ok())
+                .map(Duration::from_millis)
+                .ok_or_else(|| invalid("duration"))?;
+
+            let loads = fields
+                .map(|load| {
+                    let parsed = match load.strip_suffix('%') {
+                        Some(percent) => percent.parse::<f64>().map(|p| p / 100.0),
+                        None => load.parse::<f64>(),
+                    };
+
+                    parsed
+                        .ok()
+                        .filter(|load| (0.0..=1.0).contains(load))
+                        .ok_or_else(|| invalid("load"))
+                })
+                .collect::<error::Result<Vec<f64>>>()?;
+
+            if loads.is_empty() {
+                return Err(invalid("load"));
+            }
+
+            samples.push(LoadSample { duration, loads });
+        }
+
+        Ok(Self { samples })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct PolicyState {
+    load: f64,
+    /// Milliseconds spent at each frequency.
+    time_in_state: BTreeMap<u64, u128>,
+    total_trans: u64,
+}
+
+/// A backend that behaves like a cpufreq machine. It holds an in-memory sysfs
+/// tree of policy directories under a root, and keeps scaling_cur_freq within
+/// scaling_min_freq and scaling_max_freq, as the governor picks it for the
+/// current load. Time advances only through step and run, which also update
+/// stats/time_in_state and stats/total_trans.
+///
+/// Writes are checked the way the kernel checks them. Frequencies outside of
+/// the rated range are clamped, while unknown governors and a min above the
+/// max are rejected as invalid.
+#[derive(Debug)]
+pub struct Simulator {
+    root: PathBuf,
+    policies: Vec<SimulatedPolicy>,
+    files: InMemory,
+    state: Mutex<Vec<PolicyState>>,
+}
+
+impl Simulator {
+    /// Creates a simulator with a policyN directory under the root for each
+    /// policy, starting out under the schedutil governor with no load. Using
+    /// CPU_FREQ_PATH as the root lets code that builds paths from it run as is.
+    pub fn new(root: impl Into<PathBuf>, policies: Vec<SimulatedPolicy>) -> Self {
+        let simulator = Self {
+            root: root.into(),
+            state: Mutex::new(vec![PolicyState::default(); policies.len()]),
+            policies,
+            files: InMemory::new(),
+        };
+
+        for (number, policy) in simulator.policies.iter().enumerate() {
+            let cpus = policy
+                .cpus
+                .iter()
+                .map(|cpu| cpu.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let frequencies = policy
+                .frequencies
+                .iter()
+                .rev()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let read_only = [
+                ("affected_cpus", cpus.clone()),
+                ("related_cpus", cpus),
+                ("cpuinfo_min_freq", policy.rated_min.to_string()),
+                ("cpuinfo_max_freq", policy.rated_max.to_string()),
+                ("cpuinfo_transition_latency", "0".to_string()),
+                ("scaling_available_frequencies", frequencies),
+                ("scaling_available_governors", SIMULATED_GOVERNORS.join(" ")),
+                ("scaling_driver", SIMULATED_DRIVER.to_string()),
+                ("scaling_cur_freq", policy.rated_min.to_string()),
+                ("stats/time_in_state", String::new()),
+                ("stats/total_trans", "0".to_string()),
+            ];
+
+            let writable = [
+                ("scaling_min_freq", policy.rated_min.to_string()),
+                ("scaling_max_freq", policy.rated_max.to_string()),
+                ("scaling_governor", "schedutil".to_string()),
+                ("scaling_setspeed", "<unsupported>".to_string()),
+            ];
+
+            let dir = simulator.policy_path(number);
+
+            for (file_name, contents) in read_only {
+                simulator
+                    .files
+                    .insert_read_only(dir.join(file_name), &contents);
+            }
+
+            for (file_name, contents) in writable {
+                simulator.files.insert(dir.join(file_name), &contents);
+            }
+
+            simulator.update(number, None);
+        }
+
+        simulator
+    }
+
+    /// Returns the path of the policyN directory of the policy.
+    pub fn policy_path(&self, policy: usize) -> PathBuf {
+        self.root.join(format!("policy{}", policy))
+    }
+
+    /// Sets the load of every policy without advancing time. A single load
+    /// applies to every policy.
+    pub fn set_load(&self, loads: &[f64]) {
+        self.store_loads(loads);
+
+        for policy in 0..self.policies.len() {
+            self.update(policy, None);
+        }
+    }
+
+    /// Applies the loads, then advances time by the duration, running each
+    /// policy's governor once for the sample.
+    pub fn step(&self, loads: &[f64], duration: Duration) {
+        self.store_loads(loads);
+
+        for policy in 0..self.policies.len() {
+            self.update(policy, Some(duration));
+        }
+    }
+
+    /// Replays every sample of the trace in order.
+    pub fn run(&self, trace: &LoadTrace) {
+        for sample in &trace.samples {
+            self.step(&sample.loads, sample.duration);
+        }
+    }
+
+    /// Returns the milliseconds the policy spent at each frequency in KHz.
+    pub fn time_in_state(&self, policy: usize) -> BTreeMap<u64, u128> {
+        self.state.lock().unwrap()[policy].time_in_state.clone()
+    }
+
+    fn store_loads(&self, loads: &[f64]) {
+        let mut state = self.state.lock().unwrap();
+
+        for (i, policy_state) in state.iter_mut().enumerate() {
+            if let Some(load) = loads.get(i).or(loads.last()) {
+                policy_state.load = load.clamp(0.0, 1.0);
+            }
+        }
+    }
+
+    /// Moves the policy to the frequency its governor picks for its load,
+    /// then accounts the elapsed time to that frequency. Without elapsed time
+    /// the policy only settles into its limits and load, e.g. after a write,
+    /// and conservative, which moves one step per sample, stays put.
+    fn update(&self, policy: usize, elapsed: Option<Duration>) {
+        let Some(spec) = self.policies.get(policy) else {
+            return;
+        };
+
+        let dir = self.policy_path(policy);
+        let read_khz = |file_name: &str| self.read_khz(&dir.join(file_name));
+
+        let min = read_khz("scaling_min_freq").unwrap_or(spec.rated_min);
+        let max = read_khz("scaling_max_freq").unwrap_or(spec.rated_max);
+        let current = read_khz("scaling_cur_freq").unwrap_or(min);
+        let governor = self
+            .files
+            .contents(&dir.join("scaling_governor"))
+            .unwrap_or_default();
+
+        let mut state = self.state.lock().unwrap();
+        let policy_state = &mut state[policy];
+        let load = policy_state.load;
+
+        let target = match governor.as_str() {
+            "performance" => max,
+            "powersave" => min,
+            "userspace" => read_khz("scaling_setspeed").unwrap_or(current),
+            "ondemand" if load > ONDEMAND_UP_THRESHOLD => max,
+            "ondemand" => min + ((max - min) as f64 * load / ONDEMAND_UP_THRESHOLD) as u64,
+            "conservative" if elapsed.is_none() => current,
+            "conservative" => {
+                let step = (spec.rated_max as f64 * CONSERVATIVE_FREQ_STEP) as u64;
+
+                if load > ONDEMAND_UP_THRESHOLD {
+                    current.saturating_add(step)
+                } else if load < ONDEMAND_UP_THRESHOLD / 2.0 {
+                    current.saturating_sub(step)
+                } else {
+                    current
+                }
+            }
+            // schedutil's frequency = 1.25 * max * util.
+            _ => (1.25 * max as f64 * load) as u64,
+        };
+
+        let frequency = snap(spec, target.clamp(min, max), min, max);
+
+        if frequency != current {
+            policy_state.total_trans += 1;
+        }
+
+        *policy_state.time_in_state.entry(frequency).or_default() +=
+            elapsed.unwrap_or_default().as_millis();
+
+        // time_in_state is reported in units of 10ms, highest frequency first.
+        let time_in_state = spec
+            .frequencies
+            .iter()
+            .rev()
+            .map(|f| {
+                let ms = policy_state
+                    .time_in_state
+                    .get(f)
+                    .copied()
+                    .unwrap_or_default();
+                format!("{} {}", f, ms / 10)
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        self.files
+            .insert_read_only(dir.join("scaling_cur_freq"), &frequency.to_string());
+        self.files
+            .insert_read_only(dir.join("stats/time_in_state"), &time_in_state);
+        self.files.insert_read_only(
+            dir.join("stats/total_trans"),
+            &policy_state.total_trans.to_string(),
+        );
+    }
+
+    fn read_khz(&self, path: &Path) -> Option<u64> {
+        self.files.contents(path)?.trim().parse::<u64>().ok()
+    }
+
+    /// Returns the policy the path belongs to, and the file's name within the
+    /// policy directory.
+    fn locate<'a>(&self, path: &'a Path) -> Option<(usize, &'a str)> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let mut components = relative.iter();
+
+        let policy = components
+            .next()?
+            .to_str()?
+            .strip_prefix("policy")?
+            .parse::<usize>()
+            .ok()
+            .filter(|policy| *policy < self.policies.len())?;
+
+        Some((
+            policy,
+            relative
+                .strip_prefix(format!("policy{}", policy))
+                .ok()?
+                .to_str()?,
+        ))
+    }
+
+    /// Checks and normalizes a write the way the kernel would.
+    fn check_write(&self, policy: usize, file_name: &str, contents: &str) -> io::Result<String> {
+        let spec = &self.policies[policy];
+        let dir = self.policy_path(policy);
+        let contents = contents.trim();
+        let invalid = || io::Error::from(ErrorKind::InvalidInput);
+
+        match file_name {
+            "scaling_min_freq" | "scaling_max_freq" => {
+                let frequency = contents.parse::<u64>().map_err(|_| invalid())?;
+                let frequency = frequency.clamp(spec.rated_min, spec.rated_max);
+
+                let min = self
+                    .read_khz(&dir.join("scaling_min_freq"))
+                    .unwrap_or(spec.rated_min);
+                let max = self
+                    .read_khz(&dir.join("scaling_max_freq"))
+                    .unwrap_or(spec.rated_max);
+
+                let in_order = match file_name {
+                    "scaling_min_freq" => frequency <= max,
+                    _ => frequency >= min,
+                };
+
+                if !in_order {
+                    return Err(invalid());
+                }
+
+                Ok(frequency.to_string())
+            }
+
+            "scaling_governor" if SIMULATED_GOVERNORS.contains(&contents) => {
+                Ok(contents.to_string())
+            }
+            "scaling_governor" => Err(invalid()),
+
+            "scaling_setspeed" => {
+                let governor = self
+                    .files
+                    .contents(&dir.join("scaling_governor"))
+                    .unwrap_or_default();
+
+                if governor != "userspace" {
+                    return Err(invalid());
+                }
+
+                let frequency = contents.parse::<u64>().map_err(|_| invalid())?;
+                Ok(frequency.clamp(spec.rated_min, spec.rated_max).to_string())
+            }
+
+            _ => Ok(contents.to_string()),
+        }
+    }
+}
+
+/// Returns the lowest available frequency at or above the target, within the
+/// min and max, like the kernel's CPUFREQ_RELATION_L.
+fn snap(spec: &SimulatedPolicy, target: u64, min: u64, max: u64) -> u64 {
+    let mut frequencies: Vec<u64> = spec
+        .frequencies
+        .iter()
+        .copied()
+        .filter(|f| (min..=max).contains(f))
+        .collect();
+
+    frequencies.sort_unstable();
+
+    frequencies
+        .iter()
+        .copied()
+        .find(|f| *f >= target)
+        .or(frequencies.last().copied())
+        .unwrap_or(target)
+}
+
+impl CpufreqBackend for Simulator {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.files.read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        let Some((policy, file_name)) = self.locate(path) else {
+            return self.files.write(path, contents);
+        };
+
+        if !self.files.is_writable(path) {
+            return self.files.write(path, contents);
+        }
+
+        let contents = self.check_write(policy, file_name, contents)?;
+        self.files.write(path, &contents)?;
+
+        if file_name == "scaling_governor" && contents != "userspace" {
+            self.files
+                .insert(path.with_file_name("scaling_setspeed"), "<unsupported>");
+        }
+
+        self.update(policy, None);
+
+        Ok(())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.is_file(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.files.is_dir(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.files.read_dir(path)
+    }
+
+    fn is_writable(&self, path: &Path) -> bool {
+        self.files.is_writable(path)
+    }
+
+    fn create(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.files.create(path, contents)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        CpufreqBackend::remove(&self.files, path)
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{
+    backend::CpufreqBackend,
+    error::{self, CpmError},
+    policies::PolicyDir,
+    suggest, SMT_ACTIVE_PATH, SMT_CONTROL_PATH,
+};
+
+use std::path::Path;
+
+/// The values the smt/control file accepts. It can additionally read back as
+/// notsupported or notimplemented, which can't be written.
+pub const SMT_CONTROL_VALUES: [&str; 3] = ["on", "off", "forceoff"];
+
+/// Returns the SMT control state, e.g. on, off, forceoff or notsupported.
+pub fn read_control<B: CpufreqBackend>(backend: &B) -> error::Result<String> {
+    backend
+        .read(Path::new(SMT_CONTROL_PATH))
+        .map(|content| content.trim().to_string())
+        .map_err(|e| CpmError::from_io(Path::new(SMT_CONTROL_PATH), e))
+}
+
+/// Returns whether sibling threads are currently online.
+pub fn read_active<B: CpufreqBackend>(backend: &B) -> error::Result<bool> {
+    let content = backend
+        .read(Path::new(SMT_ACTIVE_PATH))
+        .map_err(|e| CpmError::from_io(Path::new(SMT_ACTIVE_PATH), e))?;
+
+    Ok(content.trim() == "1")
+}
+
+/// Writes the SMT control state. forceoff can't be undone until reboot.
+pub fn set_control<B: CpufreqBackend>(backend: &B, value: &str) -> error::Result<()> {
+    if !SMT_CONTROL_VALUES.contains(&value) {
+        return Err(CpmError::Parse(format!(
+            "Invalid SMT control value '{}'.{}\nValid values: {:?}",
+            value,
+            suggest::did_you_mean(value, SMT_CONTROL_VALUES),
+            SMT_CONTROL_VALUES
+        )));
+    }
+
+    let current = read_control(backend)?;
+
+    if current == "notsupported" || current == "notimplemented" {
+        return Err(CpmError::Unsupported(format!(
+            "SMT can't be controlled on this system, its state is '{}'.",
+            current
+        )));
+    }
+
+    if current == "forceoff" && value != "forceoff" {
+        return Err(CpmError::Unsupported(
+            "SMT was force disabled, and can't be enabled again until reboot.".to_string(),
+        ));
+    }
+
+    backend
+        .write(Path::new(SMT_CONTROL_PATH), value)
+        .map_err(|e| CpmError::from_io(Path::new(SMT_CONTROL_PATH), e))
+}
+
+/// Returns the number and affected CPUs of every policy, ordered by number.
+pub fn policy_cpus<B: CpufreqBackend>(
+    policy_dirs: &[PolicyDir<B>],
+) -> error::Result<Vec<(u32, Vec<u32>)>> {
+    let mut cpus: Vec<(u32, Vec<u32>)> = policy_dirs
+        .iter()
+        .map(|pd| Ok((pd.policy_number, pd.read_affected_cpus()?)))
+        .collect::<error::Result<_>>()?;
+
+    cpus.sort_by_key(|(number, _)| *number);
+
+    Ok(cpus)
+}
+
+/// Describes how the policies and their affected CPUs differ between two
+/// listings taken with policy_cpus, one line per changed policy.
+pub fn describe_changes(before: &[(u32, Vec<u32>)], after: &[(u32, Vec<u32>)]) -> Vec<String> {
+    let mut changes: Vec<String> = Vec::new();
+    let format_cpus = |cpus: &[u32]| match cpus.is_empty() {
+        true => "none".to_string(),
+        false => cpus
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(" "),
+    };
+
+    for (number, old_cpus) in before {
+        match after.iter().find(|(n, _)| n == number) {
+            None => changes.push(format!("Policy {} was removed.", number)),
+            Some((_, new_cpus)) if new_cpus != old_cpus => changes.push(format!(
+                "Policy {} affected CPUs: {} -> {}",
+                number,
+                format_cpus(old_cpus),
+                format_cpus(new_cpus)
+            )),
+            Some(_) => (),
+        }
+    }
+
+    for (number, new_cpus) in after {
+        if !before.iter().any(|(n, _)| n == number) {
+            changes.push(format!(
+                "Policy {} was added with affected CPUs: {}",
+                number,
+                format_cpus(new_cpus)
+            ));
+        }
+    }
+
+    changes
+}
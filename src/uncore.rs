@@ -0,0 +1,217 @@
+use crate::{
+    backend::{CpufreqBackend, Sysfs},
+    error::{self, CpmError},
+    frequency::Frequency,
+    UNCORE_FREQ_PATH,
+};
+
+use strum_macros::AsRefStr;
+
+use std::path::{Path, PathBuf};
+
+/// Enum representing the files present in an intel_uncore_frequency die
+/// directory, e.g. package_00_die_00/max_freq_khz. The initial_ files hold
+/// the limits the firmware set at boot, which bound the writable ones.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, AsRefStr)]
+pub enum UncoreFile {
+    current_freq_khz,
+    initial_max_freq_khz,
+    initial_min_freq_khz,
+    max_freq_khz,
+    min_freq_khz,
+}
+
+pub struct UncoreDir<B: CpufreqBackend = Sysfs> {
+    pub full_path: PathBuf,
+    pub dir_name: String,
+    pub package: u32,
+    pub die: u32,
+    /// Every file of the die is read and written through the backend.
+    pub backend: B,
+}
+
+impl UncoreDir<Sysfs> {
+    /// Creates an UncoreDir for a die directory in sysfs. See with_backend.
+    pub fn from(path: &Path) -> error::Result<Self> {
+        Self::with_backend(path, Sysfs)
+    }
+
+    /// Collects every die directory in sysfs. See collect_with_backend.
+    pub fn collect() -> error::Result<Vec<Self>> {
+        Self::collect_with_backend(Sysfs)
+    }
+}
+
+impl<B: CpufreqBackend> UncoreDir<B> {
+    /// Creates a new UncoreDir struct from a path pointing to a die directory,
+    /// whose name is expected to be in the format "package_XX_die_YY".
+    pub fn with_backend(path: &Path, backend: B) -> error::Result<Self> {
+        if !backend.is_dir(path) {
+            return Err(CpmError::NotFound {
+                path: path.to_path_buf(),
+            });
+        }
+
+        let dir_name = path
+            .file_name()
+            .ok_or(CpmError::Parse(format!(
+                "The directory name of path '{}' could not be retrieved.",
+                path.display()
+            )))?
+            .to_string_lossy()
+            .to_string();
+
+        let (package, die) = dir_name
+            .strip_prefix("package_")
+            .and_then(|rest| rest.split_once("_die_"))
+            .and_then(|(package, die)| {
+                Some((package.parse::<u32>().ok()?, die.parse::<u32>().ok()?))
+            })
+            .ok_or(CpmError::Parse(format!(
+                "The uncore directory name '{}' isn't in the format package_XX_die_YY.",
+                dir_name
+            )))?;
+
+        Ok(Self {
+            full_path: path.to_path_buf(),
+            dir_name,
+            package,
+            die,
+            backend,
+        })
+    }
+
+    /// Collects every die directory from UNCORE_FREQ_PATH, ordered by package
+    /// and die, each accessed through its own copy of the backend. Returns an
+    /// empty list when the driver isn't loaded.
+    pub fn collect_with_backend(backend: B) -> error::Result<Vec<Self>>
+    where
+        B: Clone,
+    {
+        let path = Path::new(UNCORE_FREQ_PATH);
+
+        if !backend.is_dir(path) {
+            return Ok(Vec::new());
+        }
+
+        let mut uncore_dirs: Vec<Self> = Vec::new();
+
+        let entries = backend
+            .read_dir(path)
+            .map_err(|e| CpmError::from_io(path, e))?;
+
+        for entry_path in entries {
+            let entry_name = entry_path.file_name().unwrap_or_default().to_string_lossy();
+
+            if entry_name.starts_with("package_") {
+                uncore_dirs.push(Self::with_backend(&entry_path, backend.clone())?);
+            }
+        }
+
+        uncore_dirs.sort_by_key(|ud| (ud.package, ud.die));
+
+        Ok(uncore_dirs)
+    }
+
+    pub fn read_current_freq(&self) -> error::Result<Frequency> {
+        self.read_freq(UncoreFile::current_freq_khz)
+    }
+
+    pub fn read_min_freq(&self) -> error::Result<Frequency> {
+        self.read_freq(UncoreFile::min_freq_khz)
+    }
+
+    pub fn read_max_freq(&self) -> error::Result<Frequency> {
+        self.read_freq(UncoreFile::max_freq_khz)
+    }
+
+    pub fn read_initial_min_freq(&self) -> error::Result<Frequency> {
+        self.read_freq(UncoreFile::initial_min_freq_khz)
+    }
+
+    pub fn read_initial_max_freq(&self) -> error::Result<Frequency> {
+        self.read_freq(UncoreFile::initial_max_freq_khz)
+    }
+
+    /// Sets the uncore min and max frequency. Either can be left out, and
+    /// both must fall within the initial limits. The two are written in the
+    /// order that keeps min <= max at every step.
+    pub fn set_freq_range(
+        &self,
+        min: Option<&Frequency>,
+        max: Option<&Frequency>,
+    ) -> error::Result<()> {
+        let initial_min = self.read_initial_min_freq()?.as_khz();
+        let initial_max = self.read_initial_max_freq()?.as_khz();
+
+        let desired_min = match min {
+            Some(min) => min.as_whole_khz()?,
+            None => self.read_min_freq()?.as_khz(),
+        };
+
+        let desired_max = match max {
+            Some(max) => max.as_whole_khz()?,
+            None => self.read_max_freq()?.as_khz(),
+        };
+
+        for desired in [desired_min, desired_max] {
+            if !(initial_min..=initial_max).contains(&desired) {
+                return Err(CpmError::OutOfRange {
+                    what: format!("uncore frequency (KHz) of {}", self.dir_name),
+                    requested: desired,
+                    min: initial_min,
+                    max: initial_max,
+                });
+            }
+        }
+
+        if desired_min > desired_max {
+            return Err(CpmError::OutOfRange {
+                what: format!("uncore min frequency (KHz) of {}", self.dir_name),
+                requested: desired_min,
+                min: initial_min,
+                max: desired_max,
+            });
+        }
+
+        if desired_min <= self.read_max_freq()?.as_khz() {
+            self.write(UncoreFile::min_freq_khz, &desired_min.to_string())?;
+            self.write(UncoreFile::max_freq_khz, &desired_max.to_string())
+        } else {
+            self.write(UncoreFile::max_freq_khz, &desired_max.to_string())?;
+            self.write(UncoreFile::min_freq_khz, &desired_min.to_string())
+        }
+    }
+
+    fn read_freq(&self, uncore_file: UncoreFile) -> error::Result<Frequency> {
+        let content = self.read(uncore_file.clone())?;
+        let khz = content.trim().parse::<u64>().map_err(|_| {
+            CpmError::Parse(format!(
+                "Invalid frequency '{}' in uncore file {}.",
+                content.trim(),
+                uncore_file.as_ref()
+            ))
+        })?;
+
+        Ok(Frequency::from_khz(khz))
+    }
+
+    /// Generic method for reading from an uncore file.
+    pub fn read(&self, uncore_file: UncoreFile) -> error::Result<String> {
+        let path = self.full_path.join(uncore_file.as_ref());
+
+        self.backend
+            .read(&path)
+            .map_err(|e| CpmError::from_io(&path, e))
+    }
+
+    /// Generic method for writing to an uncore file.
+    pub fn write(&self, uncore_file: UncoreFile, contents: &str) -> error::Result<()> {
+        let path = self.full_path.join(uncore_file.as_ref());
+
+        self.backend
+            .write(&path, contents)
+            .map_err(|e| CpmError::from_io(&path, e))
+    }
+}
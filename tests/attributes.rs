@@ -0,0 +1,190 @@
+use cpm::attributes::{self, Validation, Value, ATTRIBUTES};
+use cpm::{CpmError, InMemory, PolicyDir, CPU_FREQ_PATH};
+
+use std::collections::HashSet;
+use std::path::Path;
+
+/// A single policy under intel_pstate, running powersave from 0.8 to 4 GHz.
+fn machine() -> InMemory {
+    let machine = InMemory::new();
+    let dir = format!("{}policy0", CPU_FREQ_PATH);
+
+    for (file_name, contents) in [
+        ("affected_cpus", "0 1"),
+        ("cpuinfo_min_freq", "800000"),
+        ("cpuinfo_max_freq", "4000000"),
+        ("cpuinfo_transition_latency", "20000"),
+        ("scaling_available_governors", "performance powersave"),
+        (
+            "energy_performance_available_preferences",
+            "default performance power",
+        ),
+    ] {
+        machine.insert_read_only(format!("{}/{}", dir, file_name), contents);
+    }
+
+    for (file_name, contents) in [
+        ("scaling_min_freq", "800000"),
+        ("scaling_max_freq", "4000000"),
+        ("scaling_governor", "powersave"),
+        ("energy_performance_preference", "power"),
+    ] {
+        machine.insert(format!("{}/{}", dir, file_name), contents);
+    }
+
+    machine
+}
+
+fn policy_dir(machine: &InMemory) -> PolicyDir<&InMemory> {
+    PolicyDir::with_backend(&format!("{}policy0", CPU_FREQ_PATH), machine).unwrap()
+}
+
+#[test]
+fn names_are_unique_and_choices_are_registered() {
+    let file_names: HashSet<&str> = ATTRIBUTES.iter().map(|a| a.file_name).collect();
+    let aliases: HashSet<&str> = ATTRIBUTES.iter().map(|a| a.alias).collect();
+    assert_eq!(file_names.len(), ATTRIBUTES.len());
+    assert_eq!(aliases.len(), ATTRIBUTES.len());
+
+    for attribute in ATTRIBUTES {
+        if let Validation::OneOf(choices) | Validation::OneOfOrInt(choices, _) =
+            attribute.validation
+        {
+            assert!(attribute.writable, "{}", attribute.file_name);
+            assert!(file_names.contains(choices.file_name));
+        }
+    }
+}
+
+#[test]
+fn finds_attributes_by_file_name_and_alias() {
+    for name in ["gov", "gov.curr", "GOV.Current", "scaling_governor"] {
+        assert_eq!(
+            attributes::find(name).map(|a| a.file_name),
+            Some("scaling_governor"),
+            "{}",
+            name
+        );
+    }
+
+    assert_eq!(
+        attributes::find("gov.available").map(|a| a.file_name),
+        Some("scaling_available_governors")
+    );
+    assert_eq!(
+        attributes::find("freq.rated_max").map(|a| a.file_name),
+        Some("cpuinfo_max_freq")
+    );
+    assert!(attributes::find("governor").is_none());
+    assert!(attributes::find("gov.min").is_none());
+}
+
+#[test]
+fn parses_and_formats_by_kind() {
+    let frequency = attributes::SCALING_MAX_FREQ.parse("2400000\n").unwrap();
+    assert!(matches!(frequency, Value::Frequency(f) if f.as_khz() == 2_400_000));
+    assert_eq!(attributes::SCALING_MAX_FREQ.format(&frequency), "2.4 GHz");
+    assert_eq!(frequency.to_file_string().unwrap(), "2400000");
+
+    let latency = attributes::CPUINFO_TRANSITION_LATENCY
+        .parse("20000")
+        .unwrap();
+    assert_eq!(
+        attributes::CPUINFO_TRANSITION_LATENCY.format(&latency),
+        "20000 ns"
+    );
+
+    let governors = attributes::SCALING_AVAILABLE_GOVERNORS
+        .parse("performance  powersave\n")
+        .unwrap();
+    assert_eq!(governors.to_json(), r#"["performance", "powersave"]"#);
+    assert_eq!(attributes::json_string("a\"b\\c\n"), r#""a\"b\\c\n""#);
+
+    for (attribute, content) in [
+        (&attributes::SCALING_MAX_FREQ, "fast"),
+        (&attributes::SCALING_MAX_FREQ, "-1"),
+        (&attributes::CPUINFO_TRANSITION_LATENCY, "1.5"),
+    ] {
+        assert!(
+            matches!(attribute.parse(content), Err(CpmError::Parse(_))),
+            "{}",
+            content
+        );
+    }
+}
+
+#[test]
+fn parses_input_the_way_it_is_typed() {
+    let frequency = attributes::SCALING_MIN_FREQ.parse_input(" 1.2g ").unwrap();
+    assert!(matches!(frequency, Value::Frequency(f) if f.as_khz() == 1_200_000));
+
+    let governor = attributes::SCALING_GOVERNOR
+        .parse_input("PowerSave")
+        .unwrap();
+    assert_eq!(governor.to_file_string().unwrap(), "powersave");
+
+    assert!(matches!(
+        attributes::SCALING_MIN_FREQ.parse_input("1.2x"),
+        Err(CpmError::Parse(_))
+    ));
+}
+
+#[test]
+fn set_value_validates_as_the_registry_declares() {
+    let machine = machine();
+    let policy_dir = policy_dir(&machine);
+    let read = |file_name: &str| {
+        machine
+            .contents(Path::new(&format!(
+                "{}policy0/{}",
+                CPU_FREQ_PATH, file_name
+            )))
+            .unwrap()
+    };
+
+    policy_dir
+        .set_value(&attributes::SCALING_GOVERNOR, "performance")
+        .unwrap();
+    policy_dir
+        .set_value(&attributes::ENERGY_PERFORMANCE_PREFERENCE, "100")
+        .unwrap();
+    policy_dir
+        .set_value(&attributes::SCALING_MAX_FREQ, "3ghz")
+        .unwrap();
+    assert_eq!(read("scaling_governor"), "performance");
+    assert_eq!(read("energy_performance_preference"), "100");
+    assert_eq!(read("scaling_max_freq"), "3000000");
+
+    for (attribute, input) in [
+        (&attributes::SCALING_GOVERNOR, "ondemand"),
+        (&attributes::ENERGY_PERFORMANCE_PREFERENCE, "balance_power"),
+        (&attributes::CPUINFO_MAX_FREQ, "5ghz"),
+    ] {
+        assert!(
+            matches!(
+                policy_dir.set_value(attribute, input),
+                Err(CpmError::Unsupported(_))
+            ),
+            "{}",
+            input
+        );
+    }
+
+    for (attribute, input) in [
+        (&attributes::ENERGY_PERFORMANCE_PREFERENCE, "256"),
+        (&attributes::SCALING_MAX_FREQ, "5ghz"),
+        (&attributes::SCALING_MIN_FREQ, "0.5ghz"),
+    ] {
+        assert!(
+            matches!(
+                policy_dir.set_value(attribute, input),
+                Err(CpmError::OutOfRange { .. })
+            ),
+            "{}",
+            input
+        );
+    }
+
+    assert_eq!(read("scaling_max_freq"), "3000000");
+    assert_eq!(read("scaling_min_freq"), "800000");
+}
@@ -0,0 +1,838 @@
+use cpm::{CpmError, CpufreqBackend, Frequency, InMemory, PolicyDir, Recording, Simulator};
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+const CPU_PATH: &str = "/sys/devices/system/cpu";
+const CPU_FREQ_PATH: &str = "/sys/devices/system/cpu/cpufreq";
+
+/// A machine with two 0.8 to 4 GHz policies of two CPUs each, under
+/// intel_pstate with turbo enabled.
+fn machine() -> InMemory {
+    let machine = InMemory::new();
+
+    for policy in 0..2 {
+        let dir = format!("{}/policy{}", CPU_FREQ_PATH, policy);
+        let cpus = format!("{} {}", policy * 2, policy * 2 + 1);
+
+        for (file_name, contents) in [
+            ("affected_cpus", cpus.as_str()),
+            ("cpuinfo_min_freq", "800000"),
+            ("cpuinfo_max_freq", "4000000"),
+            ("scaling_available_governors", "performance powersave"),
+            (
+                "energy_performance_available_preferences",
+                "default performance balance_power",
+            ),
+        ] {
+            machine.insert_read_only(format!("{}/{}", dir, file_name), contents);
+        }
+
+        for (file_name, contents) in [
+            ("scaling_min_freq", "800000"),
+            ("scaling_max_freq", "4000000"),
+            ("scaling_governor", "powersave"),
+            ("energy_performance_preference", "balance_power"),
+        ] {
+            machine.insert(format!("{}/{}", dir, file_name), contents);
+        }
+
+        for cpu in [policy * 2, policy * 2 + 1] {
+            for state in 0..3 {
+                let dir = format!("{}/cpu{}/cpuidle/state{}", CPU_PATH, cpu, state);
+
+                for (file_name, contents) in [
+                    ("name", format!("C{}", state)),
+                    ("desc", format!("C{}", state)),
+                    ("latency", "0".to_string()),
+                    ("residency", "0".to_string()),
+                    ("usage", "0".to_string()),
+                    ("time", "0".to_string()),
+                ] {
+                    machine.insert_read_only(format!("{}/{}", dir, file_name), &contents);
+                }
+
+                machine.insert(format!("{}/disable", dir), "0");
+            }
+        }
+    }
+
+    machine.insert(format!("{}/intel_pstate/no_turbo", CPU_PATH), "0");
+
+    machine
+}
+
+fn policy_dirs(machine: &InMemory) -> Vec<PolicyDir<&InMemory>> {
+    PolicyDir::collect_with_backend(CPU_FREQ_PATH, machine).unwrap()
+}
+
+fn contents(machine: &InMemory, path: &str) -> String {
+    machine.contents(Path::new(path)).unwrap()
+}
+
+#[test]
+fn collects_policies_from_the_backend() {
+    let machine = machine();
+    let numbers: Vec<u32> = policy_dirs(&machine)
+        .iter()
+        .map(|pd| pd.policy_number)
+        .collect();
+
+    assert_eq!(numbers, [0, 1]);
+    assert_eq!(
+        policy_dirs(&machine)[1].read_affected_cpus().unwrap(),
+        [2, 3]
+    );
+}
+
+#[test]
+fn reads_and_writes_through_the_backend() {
+    let machine = machine();
+    let policy_dir = &policy_dirs(&machine)[0];
+
+    policy_dir.set_governor("performance").unwrap();
+    assert_eq!(
+        policy_dir.read_current_governor().unwrap().trim(),
+        "performance"
+    );
+    assert_eq!(
+        contents(
+            &machine,
+            "/sys/devices/system/cpu/cpufreq/policy0/scaling_governor"
+        ),
+        "performance"
+    );
+
+    policy_dir
+        .set_scaling_range(
+            Some(&Frequency::from_khz(1_000_000)),
+            Some(&Frequency::from_khz(2_000_000)),
+        )
+        .unwrap();
+    assert_eq!(
+        policy_dir.read_scaling_min_freq().unwrap().as_khz(),
+        1_000_000
+    );
+    assert_eq!(
+        policy_dir.read_scaling_max_freq().unwrap().as_khz(),
+        2_000_000
+    );
+
+    // The other policy is left alone.
+    assert_eq!(
+        contents(
+            &machine,
+            "/sys/devices/system/cpu/cpufreq/policy1/scaling_governor"
+        ),
+        "powersave"
+    );
+}
+
+#[test]
+fn rejects_writes_the_backend_refuses() {
+    let machine = machine();
+    let policy_dir = &policy_dirs(&machine)[0];
+
+    assert!(matches!(
+        policy_dir.write_raw("cpuinfo_max_freq", "5000000"),
+        Err(CpmError::PermissionDenied { .. })
+    ));
+
+    assert!(matches!(
+        policy_dir.set_scaling_range(None, Some(&Frequency::from_khz(5_000_000))),
+        Err(CpmError::OutOfRange { .. })
+    ));
+
+    assert!(policy_dir.set_governor("ondemand").is_err());
+    assert_eq!(
+        policy_dir.read_current_governor().unwrap().trim(),
+        "powersave"
+    );
+}
+
+#[test]
+fn raw_files_are_limited_to_the_policy_directory() {
+    let machine = machine();
+    machine.insert(format!("{}/policy0/boost_knob", CPU_FREQ_PATH), "0");
+    let policy_dir = &policy_dirs(&machine)[0];
+
+    let files = policy_dir.list_raw_files().unwrap();
+    assert!(files.contains(&("boost_knob".to_string(), true)));
+    assert!(files.contains(&("cpuinfo_max_freq".to_string(), false)));
+
+    policy_dir.write_raw("boost_knob", "1").unwrap();
+    assert_eq!(policy_dir.read_raw("boost_knob").unwrap(), "1");
+
+    assert!(matches!(
+        policy_dir.write_raw("cpuinfo_max_freq", "5000000"),
+        Err(CpmError::PermissionDenied { .. })
+    ));
+
+    let error = policy_dir.read_raw("boost_knb").unwrap_err();
+    assert!(matches!(error, CpmError::Unsupported(_)));
+    assert!(error.to_string().contains("Did you mean 'boost_knob'?"));
+
+    for file_name in ["../policy1/scaling_governor", "/etc/passwd", ""] {
+        assert!(
+            matches!(
+                policy_dir.write_raw(file_name, "performance"),
+                Err(CpmError::Unsupported(_))
+            ),
+            "{}",
+            file_name
+        );
+    }
+    assert_eq!(
+        contents(
+            &machine,
+            &format!("{}/policy1/scaling_governor", CPU_FREQ_PATH)
+        ),
+        "powersave"
+    );
+}
+
+#[test]
+fn fails_when_the_value_doesnt_stick() {
+    let simulator = Simulator::new(
+        CPU_FREQ_PATH,
+        vec![cpm::simulator::SimulatedPolicy::new(
+            vec![0],
+            800_000,
+            4_000_000,
+        )],
+    );
+    let policy_dir =
+        PolicyDir::with_backend(&format!("{}/policy0", CPU_FREQ_PATH), &simulator).unwrap();
+
+    // The simulator clamps a max above cpuinfo_max_freq, like the kernel.
+    assert!(matches!(
+        policy_dir.write_raw("scaling_max_freq", "4200000"),
+        Err(CpmError::NotApplied { .. })
+    ));
+}
+
+/// Stores EPP the way intel_pstate reports it on a CPU model that tunes
+/// balance_performance to 64: default resolves to balance_performance, and
+/// raw values read back by name when they match a preference.
+struct TunedEpp(InMemory);
+
+impl CpufreqBackend for TunedEpp {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.0.read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        if !path.ends_with("energy_performance_preference") {
+            return self.0.write(path, contents);
+        }
+
+        let resolved = match contents.trim() {
+            "default" | "64" => "balance_performance",
+            "0" => "performance",
+            "192" => "balance_power",
+            "255" => "power",
+            other => other,
+        };
+
+        self.0.write(path, resolved)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.0.is_file(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.0.is_dir(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.0.read_dir(path)
+    }
+
+    fn is_writable(&self, path: &Path) -> bool {
+        self.0.is_writable(path)
+    }
+
+    fn create(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.0.create(path, contents)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        CpufreqBackend::remove(&self.0, path)
+    }
+}
+
+#[test]
+fn epp_default_reads_back_as_what_it_resolved_to() {
+    let backend = TunedEpp(machine());
+    let policy_dir =
+        PolicyDir::with_backend(&format!("{}/policy0", CPU_FREQ_PATH), &backend).unwrap();
+
+    policy_dir.set_perf_profile("default").unwrap();
+    assert_eq!(
+        policy_dir.read_current_perf_profile().unwrap(),
+        "balance_performance"
+    );
+
+    // A raw value matching a preference reads back by name.
+    policy_dir.set_perf_profile("192").unwrap();
+    assert_eq!(
+        policy_dir.read_current_perf_profile().unwrap(),
+        "balance_power"
+    );
+
+    policy_dir.set_perf_profile("100").unwrap();
+    assert_eq!(policy_dir.read_current_perf_profile().unwrap(), "100");
+}
+
+#[test]
+fn raw_epp_may_read_back_as_the_tuned_balance_performance() {
+    let backend = TunedEpp(machine());
+    let policy_dir =
+        PolicyDir::with_backend(&format!("{}/policy0", CPU_FREQ_PATH), &backend).unwrap();
+
+    policy_dir.set_perf_profile("64").unwrap();
+    assert_eq!(
+        policy_dir.read_current_perf_profile().unwrap(),
+        "balance_performance"
+    );
+}
+
+#[test]
+fn dry_run_records_writes_without_making_them() {
+    let machine = machine();
+    let policy_dir = &policy_dirs(&machine)[0];
+    let dry_run = policy_dir.dry_run();
+
+    dry_run.set_governor("performance").unwrap();
+    dry_run
+        .set_scaling_range(None, Some(&Frequency::from_khz(3_000_000)))
+        .unwrap();
+
+    // The dry run reads back what it wrote, while the backend is unchanged.
+    assert_eq!(dry_run.read_current_governor().unwrap(), "performance");
+    assert_eq!(dry_run.read_scaling_max_freq().unwrap().as_khz(), 3_000_000);
+    assert_eq!(policy_dir.read_current_governor().unwrap(), "powersave");
+    assert_eq!(
+        policy_dir.read_scaling_max_freq().unwrap().as_khz(),
+        4_000_000
+    );
+
+    let writes = dry_run.backend.writes();
+    assert_eq!(writes.len(), 2);
+    assert!(writes[0].0.ends_with("scaling_governor"));
+    assert!(writes[1].0.ends_with("scaling_max_freq"));
+
+    policy_dir.replay(&writes).unwrap();
+    assert_eq!(policy_dir.read_current_governor().unwrap(), "performance");
+    assert_eq!(
+        policy_dir.read_scaling_max_freq().unwrap().as_khz(),
+        3_000_000
+    );
+}
+
+#[test]
+fn dry_run_checks_writes_against_the_backend() {
+    let machine = machine();
+    let policy_dir = &policy_dirs(&machine)[0];
+    let dry_run = policy_dir.dry_run();
+
+    assert!(matches!(
+        dry_run.write_raw("cpuinfo_min_freq", "900000"),
+        Err(CpmError::PermissionDenied { .. })
+    ));
+    assert!(dry_run.set_governor("ondemand").is_err());
+    assert!(dry_run.backend.writes().is_empty());
+}
+
+#[test]
+fn recording_creates_and_removes_without_the_inner_backend() {
+    let machine = machine();
+    let recording = Recording::new(&machine);
+    let state = Path::new("/run/cpm/lock.state");
+
+    recording.create(state, "boost 1\n").unwrap();
+    assert!(recording.is_file(state));
+    assert_eq!(recording.read(state).unwrap(), "boost 1\n");
+    assert!(!machine.is_file(state));
+
+    let no_turbo = Path::new("/sys/devices/system/cpu/intel_pstate/no_turbo");
+    recording.remove(no_turbo).unwrap();
+    assert!(!recording.is_file(no_turbo));
+    assert!(machine.is_file(no_turbo));
+}
+
+#[test]
+fn lock_reports_a_failed_restore_with_both_errors() {
+    let machine = machine();
+    machine.insert_read_only(
+        format!("{}/policy1/scaling_governor", CPU_FREQ_PATH),
+        "powersave",
+    );
+
+    let error = cpm::lock::lock(
+        &&machine,
+        &policy_dirs(&machine),
+        &Frequency::from_khz(2_000_000),
+        false,
+    )
+    .unwrap_err();
+
+    let CpmError::RestoreFailed {
+        error: cause,
+        restore_error,
+    } = &error
+    else {
+        panic!("expected RestoreFailed, got {:?}", error);
+    };
+    assert!(matches!(**cause, CpmError::PermissionDenied { .. }));
+    assert!(matches!(**restore_error, CpmError::PermissionDenied { .. }));
+    assert_eq!(error.exit_code(), 3);
+
+    // The state stays behind, so that unlock can retry.
+    assert!(machine.is_file(Path::new(cpm::LOCK_STATE_PATH)));
+}
+
+#[cfg(feature = "cli")]
+mod commands {
+    use super::*;
+
+    use cpm::argparse::run_command;
+
+    fn run(machine: &InMemory, command: &str, args: &[&str]) -> anyhow::Result<()> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        run_command(machine, command, &mut args.into_iter())
+    }
+
+    fn read(machine: &InMemory, path: &str) -> String {
+        contents(machine, &format!("{}/{}", CPU_PATH, path))
+    }
+
+    #[test]
+    fn lock_and_unlock_restore_everything() {
+        let machine = machine();
+
+        run(&machine, "lock", &["2g", "--no-idle"]).unwrap();
+
+        for policy in ["policy0", "policy1"] {
+            assert_eq!(
+                read(&machine, &format!("cpufreq/{}/scaling_governor", policy)),
+                "performance"
+            );
+            assert_eq!(
+                read(&machine, &format!("cpufreq/{}/scaling_min_freq", policy)),
+                "2000000"
+            );
+            assert_eq!(
+                read(&machine, &format!("cpufreq/{}/scaling_max_freq", policy)),
+                "2000000"
+            );
+        }
+
+        assert_eq!(read(&machine, "intel_pstate/no_turbo"), "1");
+        assert_eq!(read(&machine, "cpu3/cpuidle/state1/disable"), "0");
+        assert_eq!(read(&machine, "cpu3/cpuidle/state2/disable"), "1");
+        assert!(machine.is_file(Path::new(cpm::LOCK_STATE_PATH)));
+
+        assert!(run(&machine, "lock", &["2g"]).is_err());
+
+        run(&machine, "unlock", &[]).unwrap();
+
+        for policy in ["policy0", "policy1"] {
+            assert_eq!(
+                read(&machine, &format!("cpufreq/{}/scaling_governor", policy)),
+                "powersave"
+            );
+            assert_eq!(
+                read(
+                    &machine,
+                    &format!("cpufreq/{}/energy_performance_preference", policy)
+                ),
+                "balance_power"
+            );
+            assert_eq!(
+                read(&machine, &format!("cpufreq/{}/scaling_min_freq", policy)),
+                "800000"
+            );
+            assert_eq!(
+                read(&machine, &format!("cpufreq/{}/scaling_max_freq", policy)),
+                "4000000"
+            );
+        }
+
+        assert_eq!(read(&machine, "intel_pstate/no_turbo"), "0");
+        assert_eq!(read(&machine, "cpu3/cpuidle/state2/disable"), "0");
+        assert!(!machine.is_file(Path::new(cpm::LOCK_STATE_PATH)));
+    }
+
+    #[test]
+    fn failed_lock_leaves_nothing_behind() {
+        let machine = machine();
+
+        assert!(run(&machine, "lock", &["5ghz"]).is_err());
+
+        assert_eq!(
+            read(&machine, "cpufreq/policy0/scaling_governor"),
+            "powersave"
+        );
+        assert_eq!(read(&machine, "intel_pstate/no_turbo"), "0");
+        assert!(!machine.is_file(Path::new(cpm::LOCK_STATE_PATH)));
+    }
+
+    #[test]
+    fn set_validates_every_policy_before_writing() {
+        let machine = machine();
+        machine.remove(Path::new(
+            "/sys/devices/system/cpu/cpufreq/policy1/energy_performance_preference",
+        ));
+
+        assert!(run(
+            &machine,
+            "set",
+            &["all", "gov", "performance", "perf", "balance_power"]
+        )
+        .is_err());
+        assert_eq!(
+            read(&machine, "cpufreq/policy0/scaling_governor"),
+            "powersave"
+        );
+
+        run(
+            &machine,
+            "set",
+            &["all", "gov", "performance", "freq", "1ghz:3ghz"],
+        )
+        .unwrap();
+
+        for policy in ["policy0", "policy1"] {
+            assert_eq!(
+                read(&machine, &format!("cpufreq/{}/scaling_governor", policy)),
+                "performance"
+            );
+            assert_eq!(
+                read(&machine, &format!("cpufreq/{}/scaling_min_freq", policy)),
+                "1000000"
+            );
+            assert_eq!(
+                read(&machine, &format!("cpufreq/{}/scaling_max_freq", policy)),
+                "3000000"
+            );
+        }
+    }
+
+    #[test]
+    fn set_applies_every_pair_or_names_what_is_wrong() {
+        let machine = machine();
+
+        run(
+            &machine,
+            "set",
+            &[
+                "0",
+                "freq",
+                "1.2:3.0",
+                "gov",
+                "performance",
+                "perf",
+                "performance",
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            read(&machine, "cpufreq/policy0/scaling_min_freq"),
+            "1200000"
+        );
+        assert_eq!(
+            read(&machine, "cpufreq/policy0/energy_performance_preference"),
+            "performance"
+        );
+        assert_eq!(
+            read(&machine, "cpufreq/policy1/scaling_governor"),
+            "powersave"
+        );
+
+        for (args, message) in [
+            (
+                &["1", "gov", "performance", "scaling_governer", "powersave"][..],
+                "Unrecognized attribute 'scaling_governer'. Did you mean 'scaling_governor'?",
+            ),
+            (
+                &["1", "gov", "performance", "perf"][..],
+                "No value specified to set perf.",
+            ),
+            (&["1", "--keep-going"][..], "No target specified to set."),
+        ] {
+            let error = run(&machine, "set", args).unwrap_err();
+            assert_eq!(error.to_string(), message);
+        }
+
+        // None of the refused commands wrote the valid pair before the error.
+        assert_eq!(
+            read(&machine, "cpufreq/policy1/scaling_governor"),
+            "powersave"
+        );
+    }
+
+    #[test]
+    fn get_prints_every_key_attribute_by_default() {
+        let machine = machine();
+        let policy_dir = &policy_dirs(&machine)[1];
+
+        assert_eq!(
+            cpm::argparse::format_all_attributes(policy_dir),
+            "Policy 1\n\n    \
+             scaling min frequency        0.8 GHz\n    \
+             scaling max frequency        4 GHz\n    \
+             current frequency            n/a\n    \
+             rated min frequency          0.8 GHz\n    \
+             rated max frequency          4 GHz\n    \
+             current governor             powersave\n    \
+             available governors          performance powersave\n    \
+             current performance profile  balance_power (192)\n    \
+             scaling driver               n/a"
+        );
+
+        run(&machine, "get", &["all"]).unwrap();
+        run(&machine, "get", &["0:1", "all"]).unwrap();
+
+        assert_eq!(
+            run(&machine, "get", &["0", "all", "extra"])
+                .unwrap_err()
+                .to_string(),
+            "Unexpected argument 'extra'."
+        );
+        assert_eq!(
+            run(&machine, "get", &["7", "all"]).unwrap_err().to_string(),
+            "No policies matched '7'."
+        );
+    }
+
+    #[test]
+    fn info_shows_every_attribute_with_missing_files_as_na() {
+        let machine = machine();
+        let policy_dir = &policy_dirs(&machine)[1];
+
+        let info = cpm::argparse::format_info(policy_dir);
+        let lines: Vec<&str> = info.lines().map(str::trim).collect();
+        assert_eq!(lines[0], "Policy 1");
+        assert_eq!(lines.len(), 2 + cpm::attributes::ATTRIBUTES.len());
+
+        for line in [
+            "affected_cpus                             2 3",
+            "scaling_driver                            n/a",
+            "cpuinfo_max_freq                          4 GHz",
+            "scaling_governor                          powersave",
+            "energy_performance_preference             balance_power",
+        ] {
+            assert!(lines.contains(&line), "{}", info);
+        }
+
+        let json = cpm::argparse::format_info_json(policy_dir);
+        assert!(json.starts_with("{\"policy\": 1, \"attributes\": {"));
+        for field in [
+            "\"affected_cpus\": [\"2\", \"3\"]",
+            "\"scaling_driver\": null",
+            "\"cpuinfo_max_freq\": 4000000",
+        ] {
+            assert!(json.contains(field), "{}", json);
+        }
+
+        run(&machine, "info", &[]).unwrap();
+        run(&machine, "info", &["1", "--json"]).unwrap();
+
+        for (args, message) in [
+            (&["7"][..], "No policies matched '7'."),
+            (&["0", "1"][..], "Unexpected argument '1'."),
+        ] {
+            assert_eq!(
+                run(&machine, "info", args).unwrap_err().to_string(),
+                message
+            );
+        }
+        assert!(run(&machine, "info", &["--yaml"]).is_err());
+    }
+
+    #[test]
+    fn raw_commands_read_and_write_any_policy_file() {
+        let machine = machine();
+        machine.insert(format!("{}/policy1/boost_knob", CPU_FREQ_PATH), "0");
+
+        run(&machine, "set", &["1", "raw", "boost_knob", "1"]).unwrap();
+        assert_eq!(read(&machine, "cpufreq/policy1/boost_knob"), "1");
+        run(&machine, "get", &["1", "raw", "boost_knob"]).unwrap();
+
+        assert_eq!(
+            run(&machine, "set", &["1", "raw", "boost_knob"])
+                .unwrap_err()
+                .to_string(),
+            "No value specified to write to 'boost_knob'."
+        );
+
+        // Policy 0 has no such file.
+        let error = run(&machine, "set", &["all", "raw", "boost_knob", "0"]).unwrap_err();
+        assert_eq!(error.downcast_ref::<CpmError>().unwrap().exit_code(), 5);
+        assert_eq!(read(&machine, "cpufreq/policy1/boost_knob"), "1");
+
+        let error = run(&machine, "set", &["0", "raw", "affected_cpus", "0"]).unwrap_err();
+        assert_eq!(error.downcast_ref::<CpmError>().unwrap().exit_code(), 3);
+    }
+
+    #[test]
+    fn set_and_get_fail_when_no_policy_matches() {
+        let machine = machine();
+
+        for (command, args) in [
+            ("set", &["99", "gov", "performance"][..]),
+            ("get", &["99", "gov"][..]),
+        ] {
+            let error = run(&machine, command, args).unwrap_err();
+            assert_eq!(error.to_string(), "No policies matched '99'.");
+        }
+    }
+
+    #[test]
+    fn invalid_ranges_are_parse_errors() {
+        let machine = machine();
+        machine.insert(
+            "/sys/devices/system/cpu/intel_uncore_frequency/package_00_die_00/max_freq_khz",
+            "2400000",
+        );
+
+        for (command, args) in [
+            ("set", &["0", "freq", "3x:"][..]),
+            ("uncore", &["set", "all", "3x:"][..]),
+        ] {
+            let error = run(&machine, command, args).unwrap_err();
+            assert!(
+                matches!(error.downcast_ref::<CpmError>(), Some(CpmError::Parse(_))),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn keep_going_reports_every_write_and_fails_with_the_first_error() {
+        let machine = machine();
+        machine.remove(Path::new(
+            "/sys/devices/system/cpu/cpufreq/policy1/energy_performance_preference",
+        ));
+
+        let error = run(
+            &machine,
+            "set",
+            &[
+                "all",
+                "freq",
+                "1ghz:3ghz",
+                "perf",
+                "performance",
+                "--keep-going",
+            ],
+        )
+        .unwrap_err();
+
+        assert!(
+            format!("{:#}", error).starts_with("1 of 4 writes failed: "),
+            "{:#}",
+            error
+        );
+        assert_eq!(error.downcast_ref::<CpmError>().unwrap().exit_code(), 2);
+
+        // The writes that could be made were.
+        for policy in ["policy0", "policy1"] {
+            assert_eq!(
+                read(&machine, &format!("cpufreq/{}/scaling_max_freq", policy)),
+                "3000000"
+            );
+        }
+        assert_eq!(
+            read(&machine, "cpufreq/policy0/energy_performance_preference"),
+            "performance"
+        );
+
+        let failed = policy_dirs(&machine)[1]
+            .set_perf_profile("performance")
+            .unwrap_err();
+        let reason = failed.to_string();
+        let results = [
+            (0, "freq".to_string(), Ok(())),
+            (0, "perf".to_string(), Ok(())),
+            (1, "freq".to_string(), Ok(())),
+            (1, "perf".to_string(), Err(failed.into())),
+        ];
+
+        assert_eq!(
+            cpm::argparse::format_set_results(&results),
+            format!(
+                "Policy   Attribute Result  Reason\n\
+                 0        freq      ok\n\
+                 0        perf      ok\n\
+                 1        freq      ok\n\
+                 1        perf      error   {}\n",
+                reason
+            )
+        );
+    }
+
+    #[test]
+    fn frequencies_below_whole_khz_are_refused() {
+        let machine = machine();
+
+        for args in [
+            &["all", "freq", "2400000500h:"][..],
+            &["all", "freq", ":2.4000005g"][..],
+            &["0", "scaling_max_freq", "2400000500h"][..],
+        ] {
+            let error = run(&machine, "set", args).unwrap_err();
+            assert!(
+                matches!(error.downcast_ref::<CpmError>(), Some(CpmError::Parse(_))),
+                "{}",
+                error
+            );
+        }
+
+        assert_eq!(read(&machine, "cpufreq/policy0/scaling_min_freq"), "800000");
+        assert_eq!(
+            read(&machine, "cpufreq/policy0/scaling_max_freq"),
+            "4000000"
+        );
+        assert!(run(&machine, "lock", &["2000000500h"]).is_err());
+        assert!(!machine.is_file(Path::new(cpm::LOCK_STATE_PATH)));
+    }
+
+    #[test]
+    fn offline_counts_each_cpu_once() {
+        let machine = machine();
+        machine.insert("/sys/devices/system/cpu/online", "0-3");
+
+        for cpu in 0..4 {
+            let dir = format!("{}/cpu{}", CPU_PATH, cpu);
+            machine.insert(format!("{}/online", dir), "1");
+            machine.insert(
+                format!("{}/topology/physical_package_id", dir),
+                &(cpu / 2).to_string(),
+            );
+        }
+
+        run(&machine, "cpu", &["offline", "1,1"]).unwrap();
+        assert_eq!(read(&machine, "cpu1/online"), "0");
+
+        machine.insert("/sys/devices/system/cpu/online", "0,2-3");
+        assert!(run(&machine, "cpu", &["offline", "0"]).is_err());
+        assert!(run(&machine, "cpu", &["offline", "2,3,3"]).is_err());
+        assert_eq!(read(&machine, "cpu3/online"), "1");
+    }
+
+    #[test]
+    fn idle_disables_states_through_the_backend() {
+        let machine = machine();
+
+        run(&machine, "idle", &["disable", "1", "C2"]).unwrap();
+
+        assert_eq!(read(&machine, "cpu1/cpuidle/state2/disable"), "0");
+        assert_eq!(read(&machine, "cpu2/cpuidle/state2/disable"), "1");
+        assert_eq!(read(&machine, "cpu3/cpuidle/state2/disable"), "1");
+        assert_eq!(read(&machine, "cpu3/cpuidle/state1/disable"), "0");
+    }
+}
@@ -0,0 +1,124 @@
+use cpm::epb::{self, EPB_MAX};
+use cpm::{CpmError, InMemory, PolicyDir, CPU_FREQ_PATH};
+
+use std::path::Path;
+
+/// A machine with one policy of two CPUs, whose bias is normal.
+fn machine() -> InMemory {
+    let machine = InMemory::new();
+    machine.insert_read_only(
+        Path::new(CPU_FREQ_PATH).join("policy0/affected_cpus"),
+        "0 1",
+    );
+
+    for cpu in 0..2 {
+        machine.insert(epb::epb_path(cpu), "6");
+    }
+
+    machine
+}
+
+fn policy_dir(machine: &InMemory) -> PolicyDir<&InMemory> {
+    PolicyDir::with_backend(&format!("{}policy0", CPU_FREQ_PATH), machine).unwrap()
+}
+
+fn contents(machine: &InMemory, cpu: u32) -> String {
+    machine.contents(&epb::epb_path(cpu)).unwrap()
+}
+
+#[test]
+fn parses_names_and_numbers() {
+    assert_eq!(epb::parse_epb("performance").unwrap(), 0);
+    assert_eq!(epb::parse_epb("Balance_Power").unwrap(), 8);
+    assert_eq!(epb::parse_epb("15").unwrap(), EPB_MAX);
+    assert_eq!(epb::epb_name(4), Some("balance-performance"));
+    assert_eq!(epb::epb_name(5), None);
+
+    for value in ["16", "-1", "turbo", ""] {
+        assert!(
+            matches!(epb::parse_epb(value), Err(CpmError::Parse(_))),
+            "{}",
+            value
+        );
+    }
+}
+
+#[test]
+fn sets_every_cpu_of_the_policy() {
+    let machine = machine();
+    let policy_dir = policy_dir(&machine);
+
+    policy_dir.set_energy_perf_bias(8).unwrap();
+
+    assert_eq!(contents(&machine, 0), "8");
+    assert_eq!(contents(&machine, 1), "8");
+    assert_eq!(
+        policy_dir.read_energy_perf_bias().unwrap(),
+        [(0, 8), (1, 8)]
+    );
+}
+
+#[test]
+fn refuses_values_above_the_max() {
+    let machine = machine();
+
+    assert!(matches!(
+        policy_dir(&machine).set_energy_perf_bias(16),
+        Err(CpmError::OutOfRange {
+            requested: 16,
+            max: 15,
+            ..
+        })
+    ));
+    assert_eq!(contents(&machine, 0), "6");
+}
+
+#[test]
+fn reports_missing_and_malformed_files() {
+    let machine = machine();
+    machine.remove(&epb::epb_path(1));
+
+    assert!(matches!(
+        epb::read_epb(&machine, 1),
+        Err(CpmError::Unsupported(_))
+    ));
+    assert!(matches!(
+        epb::set_epb(&machine, 1, 0),
+        Err(CpmError::Unsupported(_))
+    ));
+    assert!(policy_dir(&machine).read_energy_perf_bias().is_err());
+
+    machine.insert(epb::epb_path(0), "fast");
+    assert!(matches!(
+        epb::read_epb(&machine, 0),
+        Err(CpmError::Parse(_))
+    ));
+
+    // A file the kernel won't let us write.
+    machine.insert_read_only(epb::epb_path(0), "6");
+    assert!(matches!(
+        epb::set_epb(&machine, 0, 0),
+        Err(CpmError::PermissionDenied { .. })
+    ));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn epb_command_accepts_names_and_numbers() {
+    let machine = machine();
+
+    let run = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        cpm::argparse::run_command(&machine, "set", &mut args.into_iter())
+    };
+
+    run(&["0", "epb", "balance-power"]).unwrap();
+    assert_eq!(contents(&machine, 1), "8");
+
+    run(&["all", "epb", "15"]).unwrap();
+    assert_eq!(contents(&machine, 0), "15");
+
+    let error = run(&["0", "epb", "20"]).unwrap_err();
+    assert_eq!(error.downcast_ref::<CpmError>().unwrap().exit_code(), 6);
+    assert_eq!(contents(&machine, 0), "15");
+}
@@ -0,0 +1,192 @@
+use cpm::{CpmError, CpufreqBackend, Frequency, InMemory, PolicyDir, CPU_FREQ_PATH};
+
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// A policy whose CPUs are all offline: intel_pstate keeps the directory, but
+/// refuses access to its files with EBUSY.
+struct OfflinePolicy(InMemory);
+
+impl CpufreqBackend for OfflinePolicy {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        match path.ends_with("affected_cpus") {
+            true => self.0.read(path),
+            false => Err(ErrorKind::ResourceBusy.into()),
+        }
+    }
+
+    fn write(&self, _: &Path, _: &str) -> io::Result<()> {
+        Err(ErrorKind::ResourceBusy.into())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.0.is_file(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.0.is_dir(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+        self.0.read_dir(path)
+    }
+
+    fn is_writable(&self, path: &Path) -> bool {
+        self.0.is_writable(path)
+    }
+
+    fn create(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.0.create(path, contents)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        CpufreqBackend::remove(&self.0, path)
+    }
+}
+
+/// A single 0.8 to 4 GHz policy of two CPUs, whose governor file is missing.
+fn machine() -> InMemory {
+    let machine = InMemory::new();
+    let dir = format!("{}policy0", CPU_FREQ_PATH);
+
+    for (file_name, contents) in [
+        ("affected_cpus", "0 1"),
+        ("cpuinfo_min_freq", "800000"),
+        ("cpuinfo_max_freq", "4000000"),
+        ("scaling_available_governors", "performance powersave"),
+    ] {
+        machine.insert_read_only(format!("{}/{}", dir, file_name), contents);
+    }
+
+    machine.insert(format!("{}/scaling_min_freq", dir), "800000");
+    machine.insert(format!("{}/scaling_max_freq", dir), "4000000");
+
+    machine
+}
+
+fn policy_dir<B: CpufreqBackend>(backend: B) -> PolicyDir<B> {
+    PolicyDir::with_backend(&format!("{}policy0", CPU_FREQ_PATH), backend).unwrap()
+}
+
+#[test]
+fn maps_io_errors_by_kind() {
+    let path = Path::new("/sys/devices/system/cpu/cpufreq/policy0/scaling_governor");
+
+    assert!(matches!(
+        CpmError::from_io(path, ErrorKind::NotFound.into()),
+        CpmError::NotFound { path: p } if p == path
+    ));
+    assert!(matches!(
+        CpmError::from_io(path, ErrorKind::PermissionDenied.into()),
+        CpmError::PermissionDenied { .. }
+    ));
+
+    let error = CpmError::from_io(path, ErrorKind::InvalidInput.into());
+    assert!(matches!(error, CpmError::Io { .. }));
+    assert!(std::error::Error::source(&error).is_some());
+}
+
+#[test]
+fn every_variant_has_its_own_exit_code() {
+    let path = PathBuf::from("/sys");
+    let errors = [
+        CpmError::NotFound { path: path.clone() },
+        CpmError::PermissionDenied { path: path.clone() },
+        CpmError::OutOfRange {
+            what: "frequency".to_string(),
+            requested: 5,
+            min: 1,
+            max: 4,
+        },
+        CpmError::Unsupported(String::new()),
+        CpmError::Parse(String::new()),
+        CpmError::Inactive { policy: 0 },
+        CpmError::Io {
+            path: path.clone(),
+            source: ErrorKind::Other.into(),
+        },
+        CpmError::NotApplied {
+            path,
+            written: "1".to_string(),
+            actual: "0".to_string(),
+        },
+    ];
+
+    let codes: Vec<i32> = errors.iter().map(CpmError::exit_code).collect();
+    assert_eq!(codes, [2, 3, 4, 5, 6, 7, 8, 9]);
+
+    let restore_failed = CpmError::RestoreFailed {
+        error: Box::new(CpmError::Parse(String::new())),
+        restore_error: Box::new(CpmError::Inactive { policy: 0 }),
+    };
+    assert_eq!(restore_failed.exit_code(), 6);
+}
+
+#[test]
+fn policy_dir_reports_what_went_wrong() {
+    let machine = machine();
+    let policy_dir = policy_dir(&machine);
+
+    assert!(matches!(
+        policy_dir.read_current_governor(),
+        Err(CpmError::NotFound { .. })
+    ));
+    assert!(matches!(
+        policy_dir.write_raw("cpuinfo_min_freq", "900000"),
+        Err(CpmError::PermissionDenied { .. })
+    ));
+    assert!(matches!(
+        policy_dir.set_governor("schedutil"),
+        Err(CpmError::Unsupported(_))
+    ));
+
+    let error = policy_dir
+        .set_scaling_range(None, Some(&Frequency::from_khz(4_200_000)))
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "The scaling_max frequency (KHz) '4200000' is out of range '800000' to '4000000'."
+    );
+}
+
+#[test]
+fn offline_policies_are_inactive_rather_than_busy() {
+    let machine = machine();
+    let path = Path::new(CPU_FREQ_PATH).join("policy0");
+    machine.insert_read_only(path.join("affected_cpus"), "");
+    machine.insert(path.join("scaling_governor"), "powersave");
+
+    let policy_dir = policy_dir(OfflinePolicy(machine));
+
+    assert!(matches!(
+        policy_dir.read_current_governor(),
+        Err(CpmError::Inactive { policy: 0 })
+    ));
+    assert!(matches!(
+        policy_dir.read_scaling_max_freq(),
+        Err(CpmError::Inactive { policy: 0 })
+    ));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn commands_exit_with_the_code_of_the_error() {
+    let machine = machine();
+
+    let run = |command: &str, args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        cpm::argparse::run_command(&machine, command, &mut args.into_iter())
+            .unwrap_err()
+            .downcast_ref::<CpmError>()
+            .map_or(1, CpmError::exit_code)
+    };
+
+    assert_eq!(run("get", &["0", "gov"]), 2);
+    assert_eq!(run("set", &["0", "raw", "cpuinfo_max_freq", "1"]), 3);
+    assert_eq!(run("set", &["0", "freq", ":5g"]), 4);
+    assert_eq!(run("set", &["0", "gov", "schedutil"]), 5);
+    assert_eq!(run("set", &["0", "freq", "fast"]), 6);
+
+    // Usage errors aren't a CpmError.
+    assert_eq!(run("get", &[]), 1);
+}
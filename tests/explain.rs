@@ -0,0 +1,99 @@
+use cpm::explain::{self, Cause, Explanation, Sample, ThermalThrottle};
+use cpm::{Frequency, InMemory, PolicyDir};
+
+use std::time::Duration;
+
+const POLICY_PATH: &str = "/sys/devices/system/cpu/cpufreq/policy0";
+
+/// A 0.8 to 4.5 GHz policy of CPUs 0 and 1 on package 0, capped by
+/// intel_pstate's max_perf_pct at 3.6 GHz.
+fn machine(current_khz: &str) -> InMemory {
+    let machine = InMemory::new();
+
+    for (file_name, contents) in [
+        ("affected_cpus", "0 1"),
+        ("cpuinfo_min_freq", "800000"),
+        ("cpuinfo_max_freq", "4500000"),
+        ("scaling_min_freq", "800000"),
+        ("scaling_max_freq", "4500000"),
+        ("scaling_cur_freq", current_khz),
+        ("scaling_governor", "powersave"),
+    ] {
+        machine.insert(format!("{}/{}", POLICY_PATH, file_name), contents);
+    }
+
+    machine.insert("/sys/devices/system/cpu/intel_pstate/max_perf_pct", "80");
+
+    for cpu in 0..2 {
+        let dir = format!("/sys/devices/system/cpu/cpu{}", cpu);
+        machine.insert(format!("{}/topology/physical_package_id", dir), "0");
+        machine.insert(format!("{}/thermal_throttle/core_throttle_count", dir), "5");
+        machine.insert(
+            format!("{}/thermal_throttle/package_throttle_count", dir),
+            "7",
+        );
+    }
+
+    machine
+}
+
+fn throttle(cpu: u32, package: u32, core_sampled: u64, package_sampled: u64) -> ThermalThrottle {
+    ThermalThrottle {
+        cpu,
+        package: Some(package),
+        core_count: core_sampled,
+        package_count: package_sampled,
+        core_sampled,
+        package_sampled,
+    }
+}
+
+#[test]
+fn names_the_limit_at_the_current_frequency() {
+    let machine = machine("3600000");
+    let policy_dir = PolicyDir::with_backend(POLICY_PATH, &machine).unwrap();
+    let sample = Sample::take(&machine, &[0, 1], Duration::ZERO);
+
+    let explanation = explain::explain(&policy_dir, &sample).unwrap();
+
+    assert_eq!(explanation.limits[0].cap, Frequency::from_khz(3_600_000));
+    assert_eq!(
+        explanation.cause,
+        Cause::Limit(vec!["intel_pstate max_perf_pct".to_string()])
+    );
+    assert_eq!(explanation.throttling.len(), 2);
+    assert_eq!(explanation.throttle_events(), 0);
+}
+
+#[test]
+fn only_takes_the_sampled_cpus_of_the_policy() {
+    let machine = machine("2000000");
+    let policy_dir = PolicyDir::with_backend(POLICY_PATH, &machine).unwrap();
+
+    let mut sample = Sample::take(&machine, &[0, 1], Duration::ZERO);
+    sample.throttling.push(throttle(2, 1, 3, 3));
+
+    let explanation = explain::explain(&policy_dir, &sample).unwrap();
+
+    let cpus: Vec<u32> = explanation.throttling.iter().map(|t| t.cpu).collect();
+    assert_eq!(cpus, [0, 1]);
+    assert_eq!(explanation.cause, Cause::Governor);
+}
+
+#[test]
+fn counts_package_events_once_per_package() {
+    let machine = machine("2000000");
+    let policy_dir = PolicyDir::with_backend(POLICY_PATH, &machine).unwrap();
+
+    let sample = Sample {
+        duration: Duration::from_millis(250),
+        throttling: vec![throttle(0, 0, 1, 4), throttle(1, 0, 2, 4)],
+        power_limits: Vec::new(),
+    };
+
+    let explanation: Explanation = explain::explain(&policy_dir, &sample).unwrap();
+
+    assert_eq!(explanation.throttle_events(), 7);
+    assert_eq!(explanation.cause, Cause::Thermal);
+    assert!(explanation.verdict().contains("7 events"));
+}
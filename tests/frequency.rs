@@ -0,0 +1,230 @@
+use cpm::frequency::{Frequency, FrequencyRange, FrequencyUnit, Snap};
+
+use proptest::prelude::*;
+
+const UNITS: [FrequencyUnit; 4] = [
+    FrequencyUnit::GHz,
+    FrequencyUnit::MHz,
+    FrequencyUnit::KHz,
+    FrequencyUnit::Hz,
+];
+
+fn unit() -> impl Strategy<Value = FrequencyUnit> {
+    prop::sample::select(UNITS.to_vec())
+}
+
+fn frequency() -> impl Strategy<Value = Frequency> {
+    (any::<u64>(), unit()).prop_map(|(hz, unit)| Frequency::from_hz(hz).in_unit(unit))
+}
+
+/// Spells a unit symbol in a random mix of upper and lower case.
+fn spelling(unit: FrequencyUnit, cases: &[bool]) -> String {
+    unit.symbol()
+        .chars()
+        .zip(cases.iter().cycle())
+        .map(|(c, upper)| {
+            if *upper {
+                c.to_ascii_uppercase()
+            } else {
+                c.to_ascii_lowercase()
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn parses_examples() {
+    let cases = [
+        ("2.4", 2_400_000_000),
+        ("2.4g", 2_400_000_000),
+        ("2.4 GHz", 2_400_000_000),
+        ("2400mhz", 2_400_000_000),
+        ("  2,400 MHz ", 2_400_000_000),
+        ("2_400_000", 2_400_000_000),
+        ("2.4e9 Hz", 2_400_000_000),
+        ("24E8h", 2_400_000_000),
+        ("0.0024e3 GHZ", 2_400_000_000),
+        ("2400000 kHz", 2_400_000_000),
+        (".5", 500_000_000),
+    ];
+
+    for (input, hz) in cases {
+        assert_eq!(
+            input.parse::<Frequency>().map(|f| f.as_hz()),
+            Ok(hz),
+            "{}",
+            input
+        );
+    }
+}
+
+#[test]
+fn only_whole_khz_can_be_written() {
+    let frequency = "2400000500h".parse::<Frequency>().unwrap();
+
+    assert_eq!(frequency.as_khz(), 2_400_000);
+    assert!(matches!(
+        frequency.as_whole_khz(),
+        Err(cpm::CpmError::Parse(_))
+    ));
+    assert_eq!(
+        "2.4g".parse::<Frequency>().unwrap().as_whole_khz().unwrap(),
+        2_400_000
+    );
+}
+
+#[test]
+fn errors_point_at_the_offending_character() {
+    let cases = [
+        ("3.5x", 3),
+        ("3.5 GHzz", 4),
+        ("3..5", 2),
+        ("_35", 0),
+        ("3__5", 1),
+        ("3,", 1),
+        ("-3", 0),
+        ("3e", 2),
+        ("3 g 4", 4),
+        ("", 0),
+        ("1.5 Hz", 0),
+    ];
+
+    for (input, position) in cases {
+        let error = input.parse::<Frequency>().unwrap_err();
+        assert_eq!(error.position, position, "{}: {}", input, error);
+    }
+
+    let error = "2.5:3.5q".parse::<FrequencyRange>().unwrap_err();
+    assert_eq!(error.position, 7);
+
+    let error = "3.5: 2.5".parse::<FrequencyRange>().unwrap_err();
+    assert_eq!(error.position, 5);
+}
+
+#[test]
+fn parses_ranges() {
+    let range: FrequencyRange = "1.2 GHz:3,500m".parse().unwrap();
+    assert_eq!(range.min.map(|f| f.as_khz()), Some(1_200_000));
+    assert_eq!(range.max.map(|f| f.as_khz()), Some(3_500_000));
+
+    let range: FrequencyRange = ":3.5".parse().unwrap();
+    assert_eq!(range.min, None);
+
+    let range: FrequencyRange = "2.5:".parse().unwrap();
+    assert_eq!(range.max, None);
+
+    assert!(":".parse::<FrequencyRange>().is_err());
+    assert!("2.5".parse::<FrequencyRange>().is_err());
+    assert!("1:2:3".parse::<FrequencyRange>().is_err());
+}
+
+#[test]
+fn snaps_to_steps() {
+    let steps: Vec<Frequency> = [800_000, 1_500_000, 2_000_000]
+        .map(Frequency::from_khz)
+        .to_vec();
+    let snap = |input: &str, snap| {
+        input
+            .parse::<Frequency>()
+            .unwrap()
+            .snap(&steps, snap)
+            .map(|f| f.as_khz())
+    };
+
+    assert_eq!(snap("1.2", Snap::Nearest), Some(1_500_000));
+    assert_eq!(snap("1.15", Snap::Nearest), Some(800_000));
+    assert_eq!(snap("1.2", Snap::Up), Some(1_500_000));
+    assert_eq!(snap("1.2", Snap::Down), Some(800_000));
+    assert_eq!(snap("2.5", Snap::Up), Some(2_000_000));
+    assert_eq!(snap("0.5", Snap::Down), Some(800_000));
+    assert_eq!(snap("1.5", Snap::Down), Some(1_500_000));
+    assert_eq!(
+        "1.2".parse::<Frequency>().unwrap().snap(&[], Snap::Nearest),
+        None
+    );
+}
+
+proptest! {
+    #[test]
+    fn display_round_trips(frequency in frequency()) {
+        let parsed: Frequency = frequency.to_string().parse().unwrap();
+        prop_assert_eq!(parsed, frequency);
+        prop_assert_eq!(parsed.unit(), frequency.unit());
+    }
+
+    #[test]
+    fn range_display_round_trips(a in frequency(), b in frequency(), open in 0..3usize) {
+        let (min, max) = (a.min(b), a.max(b));
+
+        let range = match open {
+            0 => FrequencyRange { min: Some(min), max: Some(max) },
+            1 => FrequencyRange { min: None, max: Some(max) },
+            _ => FrequencyRange { min: Some(min), max: None },
+        };
+
+        prop_assert_eq!(range.to_string().parse::<FrequencyRange>(), Ok(range));
+    }
+
+    #[test]
+    fn units_are_case_insensitive(value in 0..1_000_000u64, unit in unit(), cases in prop::collection::vec(any::<bool>(), 3)) {
+        let input = format!("{}{}", value, spelling(unit, &cases));
+        prop_assert_eq!(input.parse::<Frequency>().map(|f| f.as_hz()), Ok(value * unit.hz()));
+    }
+
+    #[test]
+    fn separators_are_ignored(value in any::<u32>(), separator in prop::sample::select(vec![",", "_"])) {
+        let digits = value.to_string();
+
+        let separated: String = digits
+            .chars()
+            .enumerate()
+            .flat_map(|(i, c)| {
+                let separate = i > 0 && (digits.len() - i) % 3 == 0;
+                separate.then_some(separator).into_iter().flat_map(str::chars).chain(Some(c))
+            })
+            .collect();
+
+        prop_assert_eq!(separated.parse::<Frequency>(), digits.parse::<Frequency>());
+    }
+
+    #[test]
+    fn scientific_notation_is_exact(mantissa in 0..1_000_000u64, exponent in 0..6u32) {
+        let input = format!("{}e{} Hz", mantissa, exponent);
+        let expected = mantissa * 10u64.pow(exponent);
+        prop_assert_eq!(input.parse::<Frequency>().map(|f| f.as_hz()), Ok(expected));
+    }
+
+    #[test]
+    fn never_panics_and_errors_point_into_the_input(input in "\\PC*") {
+        if let Err(error) = input.parse::<Frequency>() {
+            prop_assert!(error.position <= input.chars().count());
+        }
+
+        if let Err(error) = input.parse::<FrequencyRange>() {
+            prop_assert!(error.position <= input.chars().count());
+        }
+    }
+
+    #[test]
+    fn ordering_matches_hz(a in frequency(), b in frequency()) {
+        prop_assert_eq!(a.cmp(&b), a.as_hz().cmp(&b.as_hz()));
+        prop_assert_eq!(a == b, a.as_hz() == b.as_hz());
+    }
+
+    #[test]
+    fn snapping_lands_on_a_step(
+        frequency in frequency(),
+        steps in prop::collection::vec(frequency(), 1..8),
+        mode in prop::sample::select(vec![Snap::Nearest, Snap::Up, Snap::Down]),
+    ) {
+        let snapped = frequency.snap(&steps, mode).unwrap();
+        prop_assert!(steps.contains(&snapped));
+        prop_assert_eq!(snapped.unit(), frequency.unit());
+    }
+
+    #[test]
+    fn checked_arithmetic_matches_u64(a in frequency(), b in frequency()) {
+        prop_assert_eq!(a.checked_add(b).map(|f| f.as_hz()), a.as_hz().checked_add(b.as_hz()));
+        prop_assert_eq!(a.checked_sub(b).map(|f| f.as_hz()), a.as_hz().checked_sub(b.as_hz()));
+    }
+}
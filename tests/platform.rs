@@ -0,0 +1,102 @@
+use cpm::platform;
+use cpm::{CpmError, InMemory, PLATFORM_PROFILE_CHOICES_PATH, PLATFORM_PROFILE_PATH};
+
+use std::path::Path;
+
+/// A machine whose firmware offers three platform profiles.
+fn machine() -> InMemory {
+    let machine = InMemory::new();
+    machine.insert(PLATFORM_PROFILE_PATH, "balanced\n");
+    machine.insert_read_only(
+        PLATFORM_PROFILE_CHOICES_PATH,
+        "quiet balanced performance\n",
+    );
+    machine
+}
+
+fn profile(machine: &InMemory) -> String {
+    machine.contents(Path::new(PLATFORM_PROFILE_PATH)).unwrap()
+}
+
+#[test]
+fn reads_and_sets_a_supported_profile() {
+    let machine = machine();
+
+    assert_eq!(
+        platform::read_platform_profile(&machine).unwrap(),
+        "balanced"
+    );
+    assert_eq!(
+        platform::read_platform_profile_choices(&machine).unwrap(),
+        ["quiet", "balanced", "performance"]
+    );
+
+    platform::set_platform_profile(&machine, "quiet").unwrap();
+    assert_eq!(profile(&machine), "quiet");
+}
+
+#[test]
+fn refuses_profiles_the_firmware_doesnt_offer() {
+    let machine = machine();
+
+    let error = platform::set_platform_profile(&machine, "quite").unwrap_err();
+    assert!(matches!(error, CpmError::Unsupported(_)));
+    assert!(
+        error.to_string().contains("Did you mean 'quiet'?"),
+        "{}",
+        error
+    );
+    assert_eq!(profile(&machine), "balanced\n");
+
+    machine.insert_read_only(PLATFORM_PROFILE_CHOICES_PATH, "\n");
+    assert!(matches!(
+        platform::set_platform_profile(&machine, "quiet"),
+        Err(CpmError::Unsupported(_))
+    ));
+}
+
+#[test]
+fn reports_firmware_without_platform_profiles() {
+    let machine = InMemory::new();
+
+    assert!(matches!(
+        platform::read_platform_profile(&machine),
+        Err(CpmError::Unsupported(_))
+    ));
+    assert!(matches!(
+        platform::set_platform_profile(&machine, "quiet"),
+        Err(CpmError::Unsupported(_))
+    ));
+
+    // The choices can be read, but the profile itself can't be written.
+    let machine = self::machine();
+    machine.insert_read_only(PLATFORM_PROFILE_PATH, "balanced");
+    assert!(matches!(
+        platform::set_platform_profile(&machine, "quiet"),
+        Err(CpmError::PermissionDenied { .. })
+    ));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn platform_commands_get_and_set_the_profile() {
+    let machine = machine();
+    // Every command collects the cpufreq policies first.
+    machine.insert(format!("{}policy0/affected_cpus", cpm::CPU_FREQ_PATH), "0");
+
+    let run = |command: &str, args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        cpm::argparse::run_command(&machine, command, &mut args.into_iter())
+    };
+
+    run("set", &["platform", "Performance"]).unwrap();
+    assert_eq!(profile(&machine), "performance");
+
+    run("get", &["platform"]).unwrap();
+    run("get", &["platform", "avail"]).unwrap();
+    assert!(run("get", &["platform", "next"]).is_err());
+    assert!(run("set", &["platform"]).is_err());
+
+    let error = run("set", &["platform", "turbo"]).unwrap_err();
+    assert_eq!(error.downcast_ref::<CpmError>().unwrap().exit_code(), 5);
+}
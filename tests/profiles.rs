@@ -0,0 +1,127 @@
+use cpm::profiles;
+use cpm::{
+    CpmError, InMemory, CPU_FREQ_PATH, PLATFORM_PROFILE_CHOICES_PATH, PLATFORM_PROFILE_PATH,
+    PROFILES_PATH,
+};
+
+use std::path::Path;
+
+const PROFILES: &str = "
+# Fans off, clocks down.
+[quiet]
+platform = quiet
+gov = powersave
+freq = :2.0
+
+[fast]
+policies = 1
+gov = performance
+";
+
+#[test]
+fn parses_sections_and_settings() {
+    let profiles = profiles::parse_profiles(PROFILES).unwrap();
+
+    let names: Vec<&str> = profiles.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["quiet", "fast"]);
+    assert_eq!(profiles[0].get("freq"), Some(":2.0"));
+    assert_eq!(profiles[1].get("policies"), Some("1"));
+    assert_eq!(profiles[1].get("platform"), None);
+}
+
+#[test]
+fn refuses_malformed_profiles() {
+    for content in [
+        "[quiet]\ngov powersave",
+        "[quiet]\ngovernor = powersave",
+        "gov = powersave\n[quiet]",
+    ] {
+        assert!(
+            matches!(profiles::parse_profiles(content), Err(CpmError::Parse(_))),
+            "{}",
+            content
+        );
+    }
+}
+
+#[test]
+fn loads_profiles_through_the_backend() {
+    let machine = InMemory::new();
+
+    assert!(matches!(
+        profiles::load_profiles(&machine),
+        Err(CpmError::Unsupported(_))
+    ));
+
+    machine.insert(PROFILES_PATH, PROFILES);
+    assert_eq!(
+        profiles::load_profile(&machine, "fast").unwrap().name,
+        "fast"
+    );
+
+    let error = profiles::load_profile(&machine, "quite").unwrap_err();
+    assert!(matches!(error, CpmError::Unsupported(_)));
+    assert!(
+        error.to_string().contains("Did you mean 'quiet'?"),
+        "{}",
+        error
+    );
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn profile_command_sets_the_platform_profile_first() {
+    let machine = InMemory::new();
+    machine.insert(PROFILES_PATH, PROFILES);
+    machine.insert(PLATFORM_PROFILE_PATH, "balanced");
+    machine.insert_read_only(PLATFORM_PROFILE_CHOICES_PATH, "quiet balanced");
+
+    for policy in 0..2 {
+        let dir = format!("{}policy{}", CPU_FREQ_PATH, policy);
+
+        for (file_name, contents) in [
+            ("affected_cpus", policy.to_string()),
+            ("cpuinfo_min_freq", "800000".to_string()),
+            ("cpuinfo_max_freq", "4000000".to_string()),
+            (
+                "scaling_available_governors",
+                "performance powersave".to_string(),
+            ),
+        ] {
+            machine.insert_read_only(format!("{}/{}", dir, file_name), &contents);
+        }
+
+        machine.insert(format!("{}/scaling_min_freq", dir), "800000");
+        machine.insert(format!("{}/scaling_max_freq", dir), "4000000");
+        machine.insert(format!("{}/scaling_governor", dir), "performance");
+    }
+
+    let run = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        cpm::argparse::run_command(&machine, "profile", &mut args.into_iter())
+    };
+    let read = |path: &str| machine.contents(Path::new(path)).unwrap();
+
+    run(&["quiet"]).unwrap();
+    assert_eq!(read(PLATFORM_PROFILE_PATH), "quiet");
+    for policy in 0..2 {
+        let dir = format!("{}policy{}", CPU_FREQ_PATH, policy);
+        assert_eq!(read(&format!("{}/scaling_governor", dir)), "powersave");
+        assert_eq!(read(&format!("{}/scaling_max_freq", dir)), "2000000");
+    }
+
+    // Only the policies the profile names are changed.
+    run(&["fast"]).unwrap();
+    assert_eq!(
+        read(&format!("{}policy0/scaling_governor", CPU_FREQ_PATH)),
+        "powersave"
+    );
+    assert_eq!(
+        read(&format!("{}policy1/scaling_governor", CPU_FREQ_PATH)),
+        "performance"
+    );
+
+    run(&["list"]).unwrap();
+    let error = run(&["slow"]).unwrap_err();
+    assert_eq!(error.downcast_ref::<CpmError>().unwrap().exit_code(), 5);
+}
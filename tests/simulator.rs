@@ -0,0 +1,233 @@
+use cpm::simulator::{LoadTrace, SimulatedPolicy, Simulator};
+use cpm::{CpmError, CpufreqBackend, Frequency, PolicyDir, Recording};
+
+use std::time::Duration;
+
+const ROOT: &str = "/sim/cpufreq";
+
+/// A simulator with a single 0.8 to 4 GHz policy under the given governor.
+fn simulator(governor: &str) -> Simulator {
+    let simulator = Simulator::new(
+        ROOT,
+        vec![SimulatedPolicy::new(vec![0, 1], 800_000, 4_000_000)],
+    );
+
+    simulator
+        .write(&simulator.policy_path(0).join("scaling_governor"), governor)
+        .unwrap();
+
+    simulator
+}
+
+fn read(simulator: &Simulator, file_name: &str) -> String {
+    simulator
+        .read(&simulator.policy_path(0).join(file_name))
+        .unwrap()
+        .trim()
+        .to_string()
+}
+
+fn current_khz(simulator: &Simulator) -> u64 {
+    read(simulator, "scaling_cur_freq").parse().unwrap()
+}
+
+#[test]
+fn static_governors_pick_the_limits() {
+    let simulator = simulator("performance");
+    simulator.step(&[0.0], Duration::from_millis(100));
+    assert_eq!(current_khz(&simulator), 4_000_000);
+
+    let simulator = self::simulator("powersave");
+    simulator.step(&[1.0], Duration::from_millis(100));
+    assert_eq!(current_khz(&simulator), 800_000);
+}
+
+#[test]
+fn dynamic_governors_follow_the_load() {
+    let simulator = simulator("ondemand");
+    simulator.step(&[0.9], Duration::from_millis(100));
+    assert_eq!(current_khz(&simulator), 4_000_000);
+    simulator.step(&[0.4], Duration::from_millis(100));
+    assert_eq!(current_khz(&simulator), 2_400_000);
+
+    let simulator = self::simulator("schedutil");
+    simulator.step(&[0.5], Duration::from_millis(100));
+    assert_eq!(current_khz(&simulator), 2_500_000);
+}
+
+#[test]
+fn conservative_moves_one_step_per_sample() {
+    let simulator = simulator("conservative");
+
+    simulator.step(&[1.0], Duration::from_millis(100));
+    assert_eq!(current_khz(&simulator), 1_000_000);
+
+    simulator.step(&[1.0], Duration::from_millis(100));
+    assert_eq!(current_khz(&simulator), 1_200_000);
+    assert_eq!(read(&simulator, "stats/total_trans"), "2");
+
+    simulator.step(&[0.6], Duration::from_millis(100));
+    assert_eq!(current_khz(&simulator), 1_200_000);
+
+    simulator.step(&[0.1], Duration::from_millis(100));
+    assert_eq!(current_khz(&simulator), 1_000_000);
+}
+
+#[test]
+fn writes_and_loads_dont_advance_conservative() {
+    let simulator = simulator("conservative");
+    simulator.step(&[1.0], Duration::from_millis(100));
+
+    simulator.set_load(&[1.0]);
+    simulator
+        .write(&simulator.policy_path(0).join("scaling_min_freq"), "900000")
+        .unwrap();
+
+    assert_eq!(current_khz(&simulator), 1_000_000);
+    assert_eq!(read(&simulator, "stats/total_trans"), "1");
+}
+
+#[test]
+fn limits_clamp_the_current_frequency() {
+    let simulator = simulator("performance");
+    let policy_dir = PolicyDir::with_backend(&format!("{}/policy0", ROOT), &simulator).unwrap();
+
+    policy_dir
+        .set_scaling_range(None, Some(&Frequency::from_khz(3_000_000)))
+        .unwrap();
+    assert_eq!(current_khz(&simulator), 3_000_000);
+
+    // Like the kernel, a max above cpuinfo_max_freq is clamped to it.
+    simulator
+        .write(
+            &simulator.policy_path(0).join("scaling_max_freq"),
+            "4200000",
+        )
+        .unwrap();
+    assert_eq!(read(&simulator, "scaling_max_freq"), "4000000");
+    assert_eq!(current_khz(&simulator), 4_000_000);
+}
+
+#[test]
+fn range_writes_never_cross_the_current_limits() {
+    let simulator = simulator("powersave");
+    let policy_dir = PolicyDir::with_backend(&format!("{}/policy0", ROOT), &simulator).unwrap();
+    let khz = |khz: u64| Some(Frequency::from_khz(khz));
+
+    // The simulator refuses a min above the max and a max below the min, so
+    // both of these only pass when written in the right order.
+    policy_dir
+        .set_scaling_range(khz(800_000).as_ref(), khz(2_000_000).as_ref())
+        .unwrap();
+    policy_dir
+        .set_scaling_range(khz(3_000_000).as_ref(), khz(3_500_000).as_ref())
+        .unwrap();
+    assert_eq!(read(&simulator, "scaling_min_freq"), "3000000");
+    assert_eq!(read(&simulator, "scaling_max_freq"), "3500000");
+
+    policy_dir
+        .set_scaling_range(khz(1_000_000).as_ref(), khz(1_500_000).as_ref())
+        .unwrap();
+    assert_eq!(read(&simulator, "scaling_min_freq"), "1000000");
+    assert_eq!(read(&simulator, "scaling_max_freq"), "1500000");
+
+    let recording = Recording::new(&simulator);
+    let dry_run = PolicyDir::with_backend(&format!("{}/policy0", ROOT), &recording).unwrap();
+    dry_run
+        .set_scaling_range(khz(2_000_000).as_ref(), khz(2_500_000).as_ref())
+        .unwrap();
+
+    let files: Vec<String> = recording
+        .writes()
+        .iter()
+        .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(files, ["scaling_max_freq", "scaling_min_freq"]);
+}
+
+#[test]
+fn range_refusals_leave_both_limits_alone() {
+    let simulator = simulator("powersave");
+    let policy_dir = PolicyDir::with_backend(&format!("{}/policy0", ROOT), &simulator).unwrap();
+    let khz = |khz: u64| Some(Frequency::from_khz(khz));
+    policy_dir
+        .set_scaling_range(khz(2_000_000).as_ref(), khz(3_000_000).as_ref())
+        .unwrap();
+
+    for (min, max) in [
+        (khz(3_000_000), khz(2_000_000)),
+        (khz(500_000), khz(3_000_000)),
+        (None, khz(1_500_000)),
+        (khz(3_500_000), None),
+        (khz(1_000_000), khz(4_500_000)),
+    ] {
+        assert!(
+            matches!(
+                policy_dir.set_scaling_range(min.as_ref(), max.as_ref()),
+                Err(CpmError::OutOfRange { .. })
+            ),
+            "{:?}:{:?}",
+            min,
+            max
+        );
+    }
+
+    assert_eq!(read(&simulator, "scaling_min_freq"), "2000000");
+    assert_eq!(read(&simulator, "scaling_max_freq"), "3000000");
+}
+
+#[test]
+fn time_in_state_accounts_each_sample_to_its_frequency() {
+    let simulator = simulator("schedutil");
+    let trace = LoadTrace::parse("# ms load\n500 100%\n\n300 0.0\n200 0.5\n").unwrap();
+
+    simulator.run(&trace);
+
+    let time_in_state = simulator.time_in_state(0);
+    assert_eq!(time_in_state.get(&4_000_000), Some(&500));
+    assert_eq!(time_in_state.get(&800_000), Some(&300));
+    assert_eq!(time_in_state.get(&2_500_000), Some(&200));
+    assert_eq!(time_in_state.values().sum::<u128>(), 1000);
+
+    // The stats file lists every step, highest first, in units of 10ms.
+    let stats = read(&simulator, "stats/time_in_state");
+    let lines: Vec<&str> = stats.lines().collect();
+    assert_eq!(lines.len(), 33);
+    assert_eq!(lines[0], "4000000 50");
+    assert!(lines.contains(&"2500000 20"));
+    assert_eq!(lines[32], "800000 30");
+    assert_eq!(read(&simulator, "stats/total_trans"), "3");
+}
+
+#[test]
+fn loads_apply_per_policy_or_to_all() {
+    let simulator = Simulator::new(
+        ROOT,
+        vec![
+            SimulatedPolicy::new(vec![0], 800_000, 4_000_000),
+            SimulatedPolicy::new(vec![1], 800_000, 4_000_000),
+        ],
+    );
+
+    let trace = LoadTrace::parse("100 100% 0\n100 100%\n").unwrap();
+    simulator.step(&trace.samples[0].loads, trace.samples[0].duration);
+
+    let current = |policy: usize| {
+        simulator
+            .read(&simulator.policy_path(policy).join("scaling_cur_freq"))
+            .unwrap()
+    };
+
+    assert_eq!(current(0), "4000000");
+    assert_eq!(current(1), "800000");
+
+    simulator.step(&trace.samples[1].loads, trace.samples[1].duration);
+    assert_eq!(current(1), "4000000");
+}
+
+#[test]
+fn rejects_invalid_traces() {
+    for trace in ["abc 50%", "100", "100 150%", "100 -0.1", "100 half"] {
+        assert!(LoadTrace::parse(trace).is_err(), "{}", trace);
+    }
+}
@@ -0,0 +1,124 @@
+use cpm::smt::{self, SMT_CONTROL_VALUES};
+use cpm::{CpmError, InMemory, PolicyDir, CPU_FREQ_PATH, SMT_ACTIVE_PATH, SMT_CONTROL_PATH};
+
+use std::path::Path;
+
+/// A machine with SMT on, and two policies of two sibling threads each.
+fn machine() -> InMemory {
+    let machine = InMemory::new();
+    machine.insert(SMT_CONTROL_PATH, "on");
+    machine.insert_read_only(SMT_ACTIVE_PATH, "1");
+
+    for (policy, cpus) in [(0, "0 2"), (1, "1 3")] {
+        machine.insert_read_only(
+            format!("{}policy{}/affected_cpus", CPU_FREQ_PATH, policy),
+            cpus,
+        );
+    }
+
+    machine
+}
+
+fn control(machine: &InMemory) -> String {
+    machine.contents(Path::new(SMT_CONTROL_PATH)).unwrap()
+}
+
+#[test]
+fn reads_and_sets_the_control() {
+    let machine = machine();
+
+    assert_eq!(smt::read_control(&machine).unwrap(), "on");
+    assert!(smt::read_active(&machine).unwrap());
+
+    smt::set_control(&machine, "off").unwrap();
+    assert_eq!(control(&machine), "off");
+
+    for value in SMT_CONTROL_VALUES {
+        smt::set_control(&machine, value).unwrap();
+    }
+    assert_eq!(control(&machine), "forceoff");
+}
+
+#[test]
+fn refuses_values_the_control_cant_take() {
+    let machine = machine();
+
+    assert!(matches!(
+        smt::set_control(&machine, "of"),
+        Err(CpmError::Parse(_))
+    ));
+    assert!(matches!(
+        smt::set_control(&machine, "notsupported"),
+        Err(CpmError::Parse(_))
+    ));
+
+    machine.insert(SMT_CONTROL_PATH, "forceoff");
+    assert!(matches!(
+        smt::set_control(&machine, "on"),
+        Err(CpmError::Unsupported(_))
+    ));
+
+    machine.insert(SMT_CONTROL_PATH, "notsupported");
+    assert!(matches!(
+        smt::set_control(&machine, "off"),
+        Err(CpmError::Unsupported(_))
+    ));
+    assert_eq!(control(&machine), "notsupported");
+}
+
+#[test]
+fn reports_missing_files() {
+    let machine = InMemory::new();
+
+    assert!(matches!(
+        smt::read_control(&machine),
+        Err(CpmError::NotFound { .. })
+    ));
+    assert!(matches!(
+        smt::read_active(&machine),
+        Err(CpmError::NotFound { .. })
+    ));
+    assert!(smt::set_control(&machine, "off").is_err());
+}
+
+#[test]
+fn describes_how_the_policies_changed() {
+    let machine = machine();
+    let policy_dirs = PolicyDir::collect_with_backend(CPU_FREQ_PATH, &machine).unwrap();
+    let before = smt::policy_cpus(&policy_dirs).unwrap();
+    assert_eq!(before, [(0, vec![0, 2]), (1, vec![1, 3])]);
+
+    let after = [(0, vec![0]), (2, vec![4])];
+    assert_eq!(
+        smt::describe_changes(&before, &after),
+        [
+            "Policy 0 affected CPUs: 0 2 -> 0",
+            "Policy 1 was removed.",
+            "Policy 2 was added with affected CPUs: 4",
+        ]
+    );
+    assert!(smt::describe_changes(&before, &before).is_empty());
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn smt_command_sets_the_control() {
+    let machine = machine();
+
+    let run = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        cpm::argparse::run_command(&machine, "smt", &mut args.into_iter())
+    };
+
+    run(&["status"]).unwrap();
+    run(&["OFF"]).unwrap();
+    assert_eq!(control(&machine), "off");
+
+    assert!(run(&["toggle"]).is_err());
+    assert!(run(&["on", "now"]).is_err());
+
+    run(&["forceoff"]).unwrap();
+    let error = run(&["on"]).unwrap_err();
+    assert_eq!(error.downcast_ref::<CpmError>().unwrap().exit_code(), 5);
+    assert_eq!(control(&machine), "forceoff");
+}
@@ -0,0 +1,197 @@
+use cpm::uncore::UncoreDir;
+use cpm::{CpmError, Frequency, InMemory, Recording, UNCORE_FREQ_PATH};
+
+use std::path::Path;
+
+/// A machine with two packages of one die each, whose uncore may run from
+/// 0.8 to 2.4 GHz and is currently allowed all of it.
+fn machine() -> InMemory {
+    let machine = InMemory::new();
+
+    for package in 0..2 {
+        let dir = format!("{}/package_0{}_die_00", UNCORE_FREQ_PATH, package);
+
+        for (file_name, contents) in [
+            ("initial_min_freq_khz", "800000"),
+            ("initial_max_freq_khz", "2400000"),
+            ("current_freq_khz", "1600000"),
+        ] {
+            machine.insert_read_only(format!("{}/{}", dir, file_name), contents);
+        }
+
+        machine.insert(format!("{}/min_freq_khz", dir), "800000");
+        machine.insert(format!("{}/max_freq_khz", dir), "2400000");
+    }
+
+    machine
+}
+
+fn contents(machine: &InMemory, package: u32, file_name: &str) -> String {
+    let path = format!(
+        "{}/package_0{}_die_00/{}",
+        UNCORE_FREQ_PATH, package, file_name
+    );
+    machine.contents(Path::new(&path)).unwrap()
+}
+
+#[test]
+fn collects_dies_in_package_order() {
+    let machine = machine();
+    let uncore_dirs = UncoreDir::collect_with_backend(&machine).unwrap();
+
+    let dies: Vec<(u32, u32)> = uncore_dirs.iter().map(|ud| (ud.package, ud.die)).collect();
+    assert_eq!(dies, [(0, 0), (1, 0)]);
+    assert_eq!(
+        uncore_dirs[1].read_current_freq().unwrap().as_khz(),
+        1_600_000
+    );
+
+    // Without the driver there are no dies, rather than an error.
+    assert!(UncoreDir::collect_with_backend(&InMemory::new())
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn sets_a_range_within_the_initial_limits() {
+    let machine = machine();
+    let uncore_dir = &UncoreDir::collect_with_backend(&machine).unwrap()[0];
+
+    uncore_dir
+        .set_freq_range(
+            Some(&Frequency::from_khz(1_200_000)),
+            Some(&Frequency::from_khz(2_000_000)),
+        )
+        .unwrap();
+
+    assert_eq!(contents(&machine, 0, "min_freq_khz"), "1200000");
+    assert_eq!(contents(&machine, 0, "max_freq_khz"), "2000000");
+    assert_eq!(contents(&machine, 1, "max_freq_khz"), "2400000");
+
+    // Leaving out the min keeps it.
+    uncore_dir
+        .set_freq_range(None, Some(&Frequency::from_khz(1_400_000)))
+        .unwrap();
+    assert_eq!(contents(&machine, 0, "min_freq_khz"), "1200000");
+    assert_eq!(contents(&machine, 0, "max_freq_khz"), "1400000");
+}
+
+#[test]
+fn writes_max_first_when_raising_the_min_above_it() {
+    let machine = machine();
+    machine.insert(
+        format!("{}/package_00_die_00/max_freq_khz", UNCORE_FREQ_PATH),
+        "1000000",
+    );
+
+    let recording = Recording::new(&machine);
+    let uncore_dir = UncoreDir::with_backend(
+        &Path::new(UNCORE_FREQ_PATH).join("package_00_die_00"),
+        &recording,
+    )
+    .unwrap();
+
+    uncore_dir
+        .set_freq_range(
+            Some(&Frequency::from_khz(2_000_000)),
+            Some(&Frequency::from_khz(2_400_000)),
+        )
+        .unwrap();
+
+    let files: Vec<String> = recording
+        .writes()
+        .iter()
+        .map(|(path, _)| path.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(files, ["max_freq_khz", "min_freq_khz"]);
+}
+
+#[test]
+fn refuses_ranges_outside_the_limits() {
+    let machine = machine();
+    let uncore_dir = &UncoreDir::collect_with_backend(&machine).unwrap()[0];
+
+    assert!(matches!(
+        uncore_dir.set_freq_range(None, Some(&Frequency::from_khz(3_000_000))),
+        Err(CpmError::OutOfRange {
+            requested: 3_000_000,
+            min: 800_000,
+            max: 2_400_000,
+            ..
+        })
+    ));
+
+    assert!(matches!(
+        uncore_dir.set_freq_range(
+            Some(&Frequency::from_khz(2_000_000)),
+            Some(&Frequency::from_khz(1_000_000)),
+        ),
+        Err(CpmError::OutOfRange {
+            requested: 2_000_000,
+            ..
+        })
+    ));
+
+    assert_eq!(contents(&machine, 0, "min_freq_khz"), "800000");
+    assert_eq!(contents(&machine, 0, "max_freq_khz"), "2400000");
+}
+
+#[test]
+fn reports_missing_and_malformed_files() {
+    let machine = machine();
+    let die = Path::new(UNCORE_FREQ_PATH).join("package_00_die_00");
+    machine.remove(&die.join("current_freq_khz"));
+    machine.insert(die.join("initial_max_freq_khz"), "fast");
+
+    let uncore_dir = UncoreDir::with_backend(&die, &machine).unwrap();
+
+    assert!(matches!(
+        uncore_dir.read_current_freq(),
+        Err(CpmError::NotFound { .. })
+    ));
+    assert!(matches!(
+        uncore_dir.read_initial_max_freq(),
+        Err(CpmError::Parse(_))
+    ));
+    assert!(matches!(
+        UncoreDir::with_backend(
+            &Path::new(UNCORE_FREQ_PATH).join("package_07_die_00"),
+            &machine
+        ),
+        Err(CpmError::NotFound { .. })
+    ));
+}
+
+#[test]
+fn rejects_directories_not_named_after_a_die() {
+    let machine = machine();
+    machine.insert(format!("{}/uncore00/min_freq_khz", UNCORE_FREQ_PATH), "0");
+
+    assert!(matches!(
+        UncoreDir::with_backend(&Path::new(UNCORE_FREQ_PATH).join("uncore00"), &machine),
+        Err(CpmError::Parse(_))
+    ));
+}
+
+#[cfg(feature = "cli")]
+#[test]
+fn uncore_command_sets_the_selected_packages() {
+    let machine = machine();
+    // Every command collects the cpufreq policies first.
+    machine.insert(format!("{}/policy0/affected_cpus", cpm::CPU_FREQ_PATH), "0");
+
+    let run = |args: &[&str]| {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        cpm::argparse::run_command(&machine, "uncore", &mut args.into_iter())
+    };
+
+    run(&["set", "1", "1ghz:2ghz"]).unwrap();
+    assert_eq!(contents(&machine, 0, "min_freq_khz"), "800000");
+    assert_eq!(contents(&machine, 1, "min_freq_khz"), "1000000");
+    assert_eq!(contents(&machine, 1, "max_freq_khz"), "2000000");
+
+    let error = run(&["set", "all", ":3ghz"]).unwrap_err();
+    assert_eq!(error.downcast_ref::<CpmError>().unwrap().exit_code(), 4);
+
+    assert!(run(&["set", "5", "1ghz:"]).is_err());
+}